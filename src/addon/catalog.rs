@@ -2,14 +2,71 @@ use std::{env, fs};
 
 use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use serde::{Deserialize, Serialize};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CatalogSource {
     Trakt,
+    Tmdb,
+    Mdblist,
+    Stremio,
+    Letterboxd,
+    Imdb,
+    Static,
+    Anime,
+    Simkl,
+    Jellyfin,
+    Plex,
+    Rss,
+    Composite,
+}
+
+impl CatalogSource {
+    /// Sources are identified by the suffix following the base64 config in a catalog id
+    pub fn from_suffix(suffix: &str) -> Result<Self> {
+        match suffix {
+            "trakt" => Ok(CatalogSource::Trakt),
+            "tmdb" => Ok(CatalogSource::Tmdb),
+            "mdblist" => Ok(CatalogSource::Mdblist),
+            "stremio" => Ok(CatalogSource::Stremio),
+            "letterboxd" => Ok(CatalogSource::Letterboxd),
+            "imdb" => Ok(CatalogSource::Imdb),
+            "static" => Ok(CatalogSource::Static),
+            "anime" => Ok(CatalogSource::Anime),
+            "simkl" => Ok(CatalogSource::Simkl),
+            "jellyfin" => Ok(CatalogSource::Jellyfin),
+            "plex" => Ok(CatalogSource::Plex),
+            "rss" => Ok(CatalogSource::Rss),
+            "composite" => Ok(CatalogSource::Composite),
+            _ => Err(anyhow!("Unable to resolve catalog source to valid value")),
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            CatalogSource::Trakt => "trakt",
+            CatalogSource::Tmdb => "tmdb",
+            CatalogSource::Mdblist => "mdblist",
+            CatalogSource::Stremio => "stremio",
+            CatalogSource::Letterboxd => "letterboxd",
+            CatalogSource::Imdb => "imdb",
+            CatalogSource::Static => "static",
+            CatalogSource::Anime => "anime",
+            CatalogSource::Simkl => "simkl",
+            CatalogSource::Jellyfin => "jellyfin",
+            CatalogSource::Plex => "plex",
+            CatalogSource::Rss => "rss",
+            CatalogSource::Composite => "composite",
+        }
+    }
 }
 
 // First few types for building the catalog for the addons struct
+
+/// The kind of media sources are asked for upstream. Configs name a [`ContentType`], which is
+/// what Stremio is told, and map it to a kind with [`ContentType::media_kind`].
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CatalogType {
@@ -17,12 +74,61 @@ pub enum CatalogType {
     Series,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The type Stremio files a catalog under, its own types or any custom string
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ContentType {
+    Movie,
+    Series,
+    Anime,
+    Channel,
+    Tv,
+    Other(String),
+}
+
+impl ContentType {
+    /// Upstream media kind for sources without a kind of their own for the type, anime,
+    /// channels and TV are all fetched as shows
+    pub fn media_kind(&self) -> CatalogType {
+        match self {
+            ContentType::Movie => CatalogType::Movie,
+            _ => CatalogType::Series,
+        }
+    }
+}
+
+impl From<String> for ContentType {
+    fn from(content_type: String) -> Self {
+        match content_type.as_str() {
+            "movie" => ContentType::Movie,
+            "series" => ContentType::Series,
+            "anime" => ContentType::Anime,
+            "channel" => ContentType::Channel,
+            "tv" => ContentType::Tv,
+            _ => ContentType::Other(content_type),
+        }
+    }
+}
+
+impl From<ContentType> for String {
+    fn from(content_type: ContentType) -> Self {
+        match content_type {
+            ContentType::Movie => "movie".to_string(),
+            ContentType::Series => "series".to_string(),
+            ContentType::Anime => "anime".to_string(),
+            ContentType::Channel => "channel".to_string(),
+            ContentType::Tv => "tv".to_string(),
+            ContentType::Other(content_type) => content_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Extra {
     name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     options: Option<Vec<String>>,
-    #[serde(rename = "isRequired")]
+    #[serde(rename = "isRequired", default)]
     is_required: bool,
 }
 
@@ -36,36 +142,37 @@ impl Extra {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Catalog {
     id: String,
     #[serde(rename = "type")]
-    pub catalog_type: CatalogType,
+    pub catalog_type: ContentType,
     name: String,
     extra: Vec<Extra>,
 }
 
 impl Catalog {
+    #[allow(dead_code)]
     pub async fn export() -> Vec<Self> {
         // TODO: Make this build dynamically from the user config
         let catalog1 = Self {
             name: "Netflix Movies".to_string(),
             id: "eyJlbmRwb2ludCI6Ikxpc3QiLCJwYWdpbmF0aW9uIjpudWxsLCJleHRlbmRlZF9pbmZvIjp0cnVlLCJsaXN0X2lkIjoiMjA3NjQ3NzAiLCJjYXRhbG9nX3R5cGUiOiJtb3ZpZSJ9-trakt".to_string(),
-            catalog_type: CatalogType::Movie,
+            catalog_type: ContentType::Movie,
             extra: vec![Extra::new("skip", None, false)],
         };
 
         let catalog2 = Self {
             name: "Netflix TV Shows".to_string(),
             id: "eyJlbmRwb2ludCI6Ikxpc3QiLCJwYWdpbmF0aW9uIjpudWxsLCJleHRlbmRlZF9pbmZvIjp0cnVlLCJsaXN0X2lkIjoiMjA3NjQ0NzEiLCJjYXRhbG9nX3R5cGUiOiJzZXJpZXMifQ==-trakt".to_string(),
-            catalog_type: CatalogType::Series,
+            catalog_type: ContentType::Series,
             extra: vec![Extra::new("skip", None, false)],
         };
 
         let catalog3 = Self {
             name: "Broken Catalog YAY".to_string(),
             id: "elkajsdfyJlbmRwb2ludCI6Ikxpc3QiLCJwYWdpbmF0aW9uIjpudWxsLCJleHRlbmRlZF9pbmZvIjp0cnVlLCJsaXN0X2lkIjoiMjA3NjQ0NzEiLCJjYXRhbG9nX3R5cGUiOiJzZXJpZXMifQ==-trakt".to_string(),
-            catalog_type: CatalogType::Series,
+            catalog_type: ContentType::Series,
             extra: vec![Extra::new("skip", None, false)],
         };
 
@@ -82,33 +189,84 @@ impl Catalog {
         sample_catalog_list
     }
 
-    pub fn from_config(config: &str) -> Result<Vec<Self>> {
-        let config_decoded = STANDARD.decode(config).map_err(|e| {
-            anyhow!(
-                "from_b64: Error decoding 'Catalogs List' from b64 to a vec: {}",
-                e.to_string()
-            )
-        })?;
+    /// Search catalogs only answer queries, so Stremio is told `search` is required whether or
+    /// not the config listed it
+    pub fn require_search_extra(&mut self) {
+        if !self.is_search() {
+            return;
+        }
 
-        let config_decoded_str = String::from_utf8(config_decoded).map_err(|e| {
-            anyhow!(
-                "from_b64: Error converting decoded b64 value to json string: {}",
-                e.to_string()
-            )
-        })?;
+        self.extra.retain(|extra| extra.name != "search");
+        self.extra.push(Extra::new("search", None, true));
+    }
+
+    /// Fills in the options of the genre extra, adding the extra when the config didn't list
+    /// one. A genre extra from the config keeps its `isRequired` flag and any options it set.
+    pub fn add_genre_options(&mut self, options: Vec<String>) {
+        if options.is_empty() {
+            return;
+        }
 
-        let catalogs_from_config: Vec<Self> = serde_json::from_str(config_decoded_str.as_str())
-            .map_err(|e| {
-                anyhow!(
-                    "from_b64: Error converting decoded json string to TraktCatalog struct: {}",
-                    e.to_string()
-                )
-            })?;
+        match self.extra.iter_mut().find(|extra| extra.name == "genre") {
+            Some(extra) => {
+                if extra.options.as_ref().is_none_or(Vec::is_empty) {
+                    extra.options = Some(options);
+                }
+            }
+            None => self.extra.push(Extra::new("genre", Some(options), false)),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_search(&self) -> bool {
+        self.config()
+            .and_then(|config| Some(config.get("endpoint")?.as_str()? == "Search"))
+            .unwrap_or(false)
+    }
+
+    /// The catalog's own config, decoded from the base64 half of its id
+    pub fn config(&self) -> Option<Value> {
+        let (config, _) = self.id.rsplit_once('-')?;
+        decode_config(config).ok()
+    }
 
-        Ok(catalogs_from_config)
+    pub fn source(&self) -> Option<CatalogSource> {
+        let (_, suffix) = self.id.rsplit_once('-')?;
+        CatalogSource::from_suffix(suffix).ok()
     }
 }
 
+/// Configs (the manifest's catalog list and each catalog's own query) travel as base64 encoded
+/// JSON inside the URLs Stremio requests, this turns them back into their structs
+pub fn decode_config<T: DeserializeOwned>(config: &str) -> Result<T> {
+    let config_decoded = STANDARD.decode(config).map_err(|e| {
+        anyhow!(
+            "from_b64: Error decoding config from b64 to a vec: {}",
+            e.to_string()
+        )
+    })?;
+
+    let config_decoded_str = String::from_utf8(config_decoded).map_err(|e| {
+        anyhow!(
+            "from_b64: Error converting decoded b64 value to json string: {}",
+            e.to_string()
+        )
+    })?;
+
+    let config_from_json: T = serde_json::from_str(config_decoded_str.as_str()).map_err(|e| {
+        anyhow!(
+            "from_b64: Error converting decoded json string to {}: {}",
+            std::any::type_name::<T>(),
+            e.to_string()
+        )
+    })?;
+
+    Ok(config_from_json)
+}
+
 // Next few types for creating response catalog to send to Stremio
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CatalogResponse {
@@ -121,13 +279,13 @@ impl CatalogResponse {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DefaultVideoID {
     pub default_video_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Trailer {
     pub source: String,
@@ -135,11 +293,11 @@ pub struct Trailer {
     pub trailer_type: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CatalogMeta {
     #[serde(rename = "type")]
-    pub catalog_type: CatalogType,
+    pub catalog_type: ContentType,
     pub id: String,
     pub name: String,
     pub poster: Option<String>,
@@ -153,22 +311,82 @@ pub struct CatalogMeta {
     pub runtime: Option<String>,
 }
 
+impl CatalogMeta {
+    /// Bare meta for an id, sources fill in whatever else they know. Metahub only has artwork
+    /// for IMDb ids, other ids (e.g. kitsu:) start without any.
+    pub fn from_id(id: String, name: String, catalog_type: ContentType) -> Self {
+        let has_metahub_artwork = id.starts_with("tt");
+        let metahub_image = |kind: &str| {
+            has_metahub_artwork
+                .then(|| format!("https://images.metahub.space/{}/medium/{}/img", kind, id))
+        };
+
+        let poster = metahub_image("poster");
+        let background = metahub_image("background");
+        let logo = metahub_image("logo");
+
+        let behavior_hints = DefaultVideoID {
+            default_video_id: id.clone(),
+        };
+
+        Self {
+            id,
+            name,
+            catalog_type,
+            genres: None,
+            release_info: None,
+            background,
+            poster,
+            description: None,
+            behavior_hints: Some(behavior_hints),
+            trailer: None,
+            logo,
+            runtime: None,
+        }
+    }
+}
+
 // Following types used for parsing incoming requests from Stremio to the API
+
+/// Items per page, every source is asked for pages of this size
+pub const PAGE_SIZE: i32 = 100;
+
+// Everything but unreserved characters, so `&`, `/` and `=` in a value can't split a path
+const PATH_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+fn decode_path_value(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+/// Encodes a catalog id or extra value for a catalog path, the reverse of what `from_path` decodes
+pub fn encode_path_value(value: &str) -> String {
+    utf8_percent_encode(value, PATH_VALUE).to_string()
+}
+
 #[derive(Debug)]
 pub struct CatalogRequestParams {
     pub catalog_id: String,
     pub pagination: PaginationDetails,
     pub genre: Option<String>,
+    pub search: Option<String>,
     pub source: CatalogSource,
 }
 
 impl CatalogRequestParams {
+    /// Parses a catalog path as it was requested, still percent-encoded. The path is split on
+    /// `/` and `&` before names and values are decoded, so a search for "AC/DC" or a genre like
+    /// "Action & Adventure" stays whole.
     pub fn from_path(catalog_path: &str) -> Result<Self> {
         // four scenarios for the catalog_path
         // normal request:           /:config/catalog/:catalog_type/catalog_id.json
         // with pagination:          /:config/catalog/:catalog_type/catalog_id/skip=200.json
         // with genres:              /:config/catalog/:catalog_type/catalog_id/genre=Adventure.json
         // with genres + pagination: /:config/catalog/:catalog_type/catalog_id/skip=43&genre=2024.json
+        // with search:              /:config/catalog/:catalog_type/catalog_id/search=the matrix.json
 
         let catalog_path_segments: Vec<&str> = catalog_path
             .strip_suffix(".json")
@@ -176,20 +394,21 @@ impl CatalogRequestParams {
             .split("/")
             .collect();
 
-        const PAGE_SIZE: i32 = 100;
         let mut skip = None;
         let mut genre = None;
+        let mut search = None;
 
         // Separate source and catalog_id from path
-        let catalog_id_and_source: Vec<String> = catalog_path_segments[0]
+        let catalog_id_and_source: Vec<String> = decode_path_value(catalog_path_segments[0])
             .split("-")
             .map(|id| id.to_string())
             .collect();
 
-        let catalog_source = match catalog_id_and_source[1].as_str() {
-            "trakt" => Ok(CatalogSource::Trakt),
-            _ => Err(anyhow!("Unable to resolve catalog source to valid value")),
-        }?;
+        let catalog_source = CatalogSource::from_suffix(
+            catalog_id_and_source
+                .get(1)
+                .context("Catalog id has no source suffix")?,
+        )?;
 
         match &catalog_path_segments.len() {
             1 => Ok(CatalogRequestParams {
@@ -197,8 +416,10 @@ impl CatalogRequestParams {
                 pagination: PaginationDetails {
                     page: 1,
                     page_size: PAGE_SIZE,
+                    skip: 0,
                 },
                 genre: None,
+                search: None,
                 source: catalog_source,
             }),
             2 => {
@@ -209,16 +430,20 @@ impl CatalogRequestParams {
                     .collect();
 
                 for param in &other_catalog_params {
-                    let parts: Vec<&str> = param.split("=").collect();
-                    if parts.len() == 2 {
-                        match parts[0] {
+                    // Split on the first `=` only, search queries can contain their own
+                    if let Some((name, value)) = param.split_once("=") {
+                        let value = decode_path_value(value);
+                        match decode_path_value(name).as_str() {
                             "skip" => {
                                 skip =
-                                    Some(parts[1].parse::<i32>().map_err(|e| {
+                                    Some(value.parse::<i32>().map_err(|e| {
                                         anyhow!("Unable to parse skip value: {}", e)
                                     })?)
                             }
-                            "genre" => genre = Some(parts[1].to_string()),
+                            "genre" => genre = Some(value),
+                            "search" if !value.trim().is_empty() => {
+                                search = Some(value.trim().to_string())
+                            }
                             _ => {}
                         }
                     }
@@ -235,8 +460,10 @@ impl CatalogRequestParams {
                     pagination: PaginationDetails {
                         page,
                         page_size: PAGE_SIZE,
+                        skip: skip.unwrap_or(0),
                     },
                     genre,
+                    search,
                     source: catalog_source,
                 })
             }
@@ -249,4 +476,75 @@ impl CatalogRequestParams {
 pub struct PaginationDetails {
     pub page: i32,
     pub page_size: i32,
+    // Raw skip sent by Stremio, for sources whose pages don't line up with PAGE_SIZE
+    pub skip: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_catalog_path() {
+        let params = CatalogRequestParams::from_path("eyJhIjoxfQ==-trakt.json").unwrap();
+
+        assert_eq!(params.catalog_id, "eyJhIjoxfQ==");
+        assert_eq!(params.source, CatalogSource::Trakt);
+        assert_eq!(params.pagination.skip, 0);
+        assert_eq!(params.genre, None);
+        assert_eq!(params.search, None);
+    }
+
+    #[test]
+    fn catalog_id_is_decoded() {
+        let params = CatalogRequestParams::from_path("ab%2Bc%2Fd%3D%3D-tmdb.json").unwrap();
+        assert_eq!(params.catalog_id, "ab+c/d==");
+    }
+
+    #[test]
+    fn extras_are_split_before_decoding() {
+        let params =
+            CatalogRequestParams::from_path("abc-tmdb/search=Fast%20%26%20Furious.json").unwrap();
+        assert_eq!(params.search.as_deref(), Some("Fast & Furious"));
+
+        let params = CatalogRequestParams::from_path("abc-tmdb/search=AC%2FDC.json").unwrap();
+        assert_eq!(params.search.as_deref(), Some("AC/DC"));
+
+        let params = CatalogRequestParams::from_path("abc-tmdb/search=1%2B1%3D2.json").unwrap();
+        assert_eq!(params.search.as_deref(), Some("1+1=2"));
+    }
+
+    #[test]
+    fn genre_and_skip_together() {
+        let params = CatalogRequestParams::from_path(
+            "abc-tmdb/skip=200&genre=Action%20%26%20Adventure.json",
+        )
+        .unwrap();
+
+        assert_eq!(params.genre.as_deref(), Some("Action & Adventure"));
+        assert_eq!(params.pagination.skip, 200);
+        assert_eq!(params.pagination.page, 3);
+    }
+
+    #[test]
+    fn encoded_values_round_trip() {
+        let path = format!(
+            "{}-tmdb/skip=100&genre={}.json",
+            encode_path_value("ab+c/d=="),
+            encode_path_value("Action & Adventure")
+        );
+        let params = CatalogRequestParams::from_path(&path).unwrap();
+
+        assert_eq!(params.catalog_id, "ab+c/d==");
+        assert_eq!(params.genre.as_deref(), Some("Action & Adventure"));
+        assert_eq!(params.pagination.skip, 100);
+    }
+
+    #[test]
+    fn rejects_bad_paths() {
+        assert!(CatalogRequestParams::from_path("abc-trakt").is_err());
+        assert!(CatalogRequestParams::from_path("abc-nowhere.json").is_err());
+        assert!(CatalogRequestParams::from_path("abc-trakt/skip=x.json").is_err());
+        assert!(CatalogRequestParams::from_path("abc-trakt/a/b.json").is_err());
+    }
 }
@@ -1,8 +1,99 @@
-use super::catalog::{Catalog, CatalogType};
-use anyhow::anyhow;
+use super::catalog::{decode_config, Catalog, CatalogSource, ContentType};
+use crate::genres::genre_options;
 use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::collections::HashSet;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+const ADDON_ID: &str = "com.dynamic.catalogs";
+const ADDON_NAME: &str = "Dynamic Catalogs";
+// Stremio waits on the manifest, a source slower than this goes without a genre dropdown
+const GENRE_OPTIONS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What an install is configured with. Older installs carry only their catalog list, newer
+/// ones can also name the addon.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum InstallConfig {
+    Catalogs(Vec<Catalog>),
+    Addon(AddonConfig),
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AddonConfig {
+    // Tells installs apart in Stremio, which replaces an addon installed again under the same
+    // id. Generated once when an install is first configured and carried along from then on,
+    // configs from before that get an id derived from their catalogs instead.
+    id: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    logo: Option<String>,
+    background: Option<String>,
+    #[serde(default)]
+    catalogs: Vec<Catalog>,
+    // Installs from before configs could name the addon, they keep the id they were installed
+    // under
+    #[serde(skip)]
+    bare_catalog_list: bool,
+}
+
+impl AddonConfig {
+    /// Config a new install starts from, an empty catalog list under an id of its own
+    pub fn new_install() -> String {
+        let config = json!({ "id": Uuid::new_v4().simple().to_string(), "catalogs": [] });
+        STANDARD.encode(config.to_string())
+    }
+
+    pub fn from_config(config: &str) -> Result<Self> {
+        match decode_config(config)? {
+            InstallConfig::Catalogs(catalogs) => Ok(Self {
+                catalogs,
+                bare_catalog_list: true,
+                ..Self::default()
+            }),
+            InstallConfig::Addon(addon_config) => Ok(addon_config),
+        }
+    }
+
+    fn addon_id(&self) -> String {
+        let suffix = self
+            .id
+            .as_deref()
+            .map(id_segment)
+            .filter(|segment| !segment.is_empty());
+
+        match suffix {
+            Some(suffix) => format!("{}.{}", ADDON_ID, suffix),
+            None if self.bare_catalog_list => ADDON_ID.to_string(),
+            None => format!("{}.{}", ADDON_ID, self.catalogs_digest()),
+        }
+    }
+
+    // Short hash of the catalog ids, which hold each catalog's whole config
+    fn catalogs_digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for catalog in &self.catalogs {
+            hasher.update(catalog.id());
+            hasher.update("\n");
+        }
+        format!("{:x}", hasher.finalize())[..16].to_string()
+    }
+}
+
+// Lowercase letters and digits joined by dashes, e.g. "Kids' Movies" becomes "kids-movies"
+fn id_segment(value: &str) -> String {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -11,39 +102,124 @@ pub struct Manifest {
     version: String,
     name: String,
     description: String,
-    logo: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<String>,
     resources: Vec<Resource>,
-    types: Vec<CatalogType>,
+    types: Vec<ContentType>,
     catalogs: Vec<Catalog>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    id_prefixes: Vec<String>,
+    behavior_hints: BehaviorHints,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BehaviorHints {
+    // Stremio shows a gear icon opening `/configure` next to the manifest
+    configurable: bool,
+    // Stremio won't install the addon until it has been configured
+    configuration_required: bool,
 }
 
 impl Manifest {
+    /// Manifest served without a config, it only points Stremio at the dashboard
+    pub fn unconfigured() -> Self {
+        Self {
+            id: ADDON_ID.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            name: ADDON_NAME.to_string(),
+            description: ADDON_NAME.to_string(),
+            logo: None,
+            background: None,
+            resources: vec![Resource::Catalog, Resource::Meta],
+            types: vec![ContentType::Movie, ContentType::Series],
+            catalogs: Vec::new(),
+            id_prefixes: Vec::new(),
+            behavior_hints: BehaviorHints {
+                configurable: true,
+                configuration_required: true,
+            },
+        }
+    }
+
     pub async fn build(config: &str) -> Result<Self> {
         // let catalogs = Catalog::export().await;
-        let catalogs = Catalog::from_config(config)?;
-        let catalog_types: Vec<CatalogType> = catalogs
+        let addon_config = AddonConfig::from_config(config)?;
+        let id = addon_config.addon_id();
+        let AddonConfig {
+            name,
+            description,
+            logo,
+            background,
+            mut catalogs,
+            ..
+        } = addon_config;
+        catalogs.iter_mut().for_each(Catalog::require_search_extra);
+
+        // A source failing to list its genres only costs its catalog the genre dropdown
+        let mut lookups = JoinSet::new();
+        for (position, catalog) in catalogs.iter().enumerate() {
+            let catalog = catalog.clone();
+            lookups.spawn(async move {
+                let options =
+                    tokio::time::timeout(GENRE_OPTIONS_TIMEOUT, genre_options(&catalog)).await;
+                (position, options)
+            });
+        }
+        while let Some(lookup) = lookups.join_next().await {
+            let (position, options) = lookup?;
+            match options {
+                Ok(Ok(Some(options))) => catalogs[position].add_genre_options(options),
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => println!("Unable to get genre options for a catalog: {}", e),
+                Err(_) => println!("Timed out getting genre options for a catalog"),
+            }
+        }
+        let catalog_types: Vec<ContentType> = catalogs
             .iter()
-            .map(|x| x.catalog_type)
+            .map(|x| x.catalog_type.clone())
             .collect::<HashSet<_>>()
             .into_iter()
             .collect();
 
         let resources = {
             if !catalogs.is_empty() {
-                vec![Resource::Catalog]
+                vec![Resource::Catalog, Resource::Meta]
             } else {
                 vec![]
             }
         };
+        // Ids meta is served for. Anime catalogs fall back to kitsu: ids, everything else is IMDb
+        let mut id_prefixes = Vec::new();
+        if !catalogs.is_empty() {
+            id_prefixes.push("tt".to_string());
+        }
+        if catalogs
+            .iter()
+            .any(|catalog| matches!(catalog.source(), Some(CatalogSource::Anime)))
+        {
+            id_prefixes.push("kitsu:".to_string());
+        }
+
+        let name = name.unwrap_or_else(|| ADDON_NAME.to_string());
         Ok(Self {
-            id: "com.dynamic.catalogs".to_string(),
-            version: "0.0.1".to_string(),
-            name: "Dynamic Catalogs".to_string(),
-            description: "Dynamic Catalogs".to_string(),
-            logo: "logo.png".to_string(),
+            id,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: description.unwrap_or_else(|| name.clone()),
+            name,
+            logo,
+            background,
             resources,
             types: catalog_types,
             catalogs,
+            id_prefixes,
+            // The gear icon reopens the dashboard under this install's config
+            behavior_hints: BehaviorHints {
+                configurable: true,
+                configuration_required: false,
+            },
         })
     }
 }
@@ -52,4 +228,64 @@ impl Manifest {
 #[serde(rename_all = "lowercase")]
 pub enum Resource {
     Catalog,
+    Meta,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addon_id(config: serde_json::Value) -> String {
+        let config = STANDARD.encode(config.to_string());
+        AddonConfig::from_config(&config).unwrap().addon_id()
+    }
+
+    fn catalogs(ids: &[&str]) -> serde_json::Value {
+        ids.iter()
+            .map(|id| json!({ "id": id, "type": "movie", "name": id, "extra": [] }))
+            .collect()
+    }
+
+    #[test]
+    fn renaming_an_install_keeps_its_id() {
+        let before = addon_id(json!({ "name": "Movies", "catalogs": catalogs(&["a-trakt"]) }));
+        let after = addon_id(json!({ "name": "Films", "catalogs": catalogs(&["a-trakt"]) }));
+
+        assert_eq!(before, after);
+        assert!(before.starts_with(&format!("{}.", ADDON_ID)));
+    }
+
+    #[test]
+    fn installs_sharing_a_name_get_their_own_ids() {
+        let first = addon_id(json!({ "name": "Movies", "catalogs": catalogs(&["a-trakt"]) }));
+        let second = addon_id(json!({ "name": "Movies", "catalogs": catalogs(&["b-trakt"]) }));
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn configured_id_wins() {
+        let id = addon_id(json!({ "id": "Kids' Movies", "catalogs": catalogs(&["a-trakt"]) }));
+        assert_eq!(id, format!("{}.kids-movies", ADDON_ID));
+    }
+
+    #[test]
+    fn new_installs_keep_their_generated_id() {
+        let config = AddonConfig::from_config(&AddonConfig::new_install()).unwrap();
+        let id = config.addon_id();
+
+        assert!(config.catalogs.is_empty());
+        assert_ne!(id, format!("{}.{}", ADDON_ID, config.catalogs_digest()));
+        assert_ne!(
+            id,
+            AddonConfig::from_config(&AddonConfig::new_install())
+                .unwrap()
+                .addon_id()
+        );
+    }
+
+    #[test]
+    fn bare_catalog_lists_keep_the_original_id() {
+        assert_eq!(addon_id(catalogs(&["a-trakt"])), ADDON_ID);
+    }
 }
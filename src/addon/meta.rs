@@ -0,0 +1,123 @@
+use super::catalog::{ContentType, DefaultVideoID, Trailer};
+use serde::{Deserialize, Serialize};
+
+// Types for the meta resource, the full detail page of a single movie or show
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetaResponse {
+    pub meta: MetaDetail,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaDetail {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub catalog_type: ContentType,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_info: Option<String>,
+    // ISO 8601
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cast: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub director: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub writer: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub website: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trailers: Vec<Trailer>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<MetaLink>,
+    // Seasons and episodes of a series, empty for movies
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub videos: Vec<Video>,
+    // Only set for movies, a default video makes Stremio hide the episode list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behavior_hints: Option<DefaultVideoID>,
+}
+
+impl MetaDetail {
+    /// Bare meta for an id, metahub artwork is only available for IMDb ids
+    pub fn from_id(id: String, name: String, catalog_type: ContentType) -> Self {
+        let has_metahub_artwork = id.starts_with("tt");
+        let metahub_image = |kind: &str| {
+            has_metahub_artwork
+                .then(|| format!("https://images.metahub.space/{}/medium/{}/img", kind, id))
+        };
+
+        Self {
+            poster: metahub_image("poster"),
+            background: metahub_image("background"),
+            logo: metahub_image("logo"),
+            id,
+            catalog_type,
+            name,
+            description: None,
+            release_info: None,
+            released: None,
+            genres: Vec::new(),
+            cast: Vec::new(),
+            director: Vec::new(),
+            writer: Vec::new(),
+            runtime: None,
+            country: None,
+            language: None,
+            website: None,
+            trailers: Vec::new(),
+            links: Vec::new(),
+            videos: Vec::new(),
+            behavior_hints: None,
+        }
+    }
+}
+
+/// Shown on the detail page grouped by category, e.g. Cast or Directors
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetaLink {
+    pub name: String,
+    pub category: String,
+    pub url: String,
+}
+
+impl MetaLink {
+    pub fn new(name: &str, category: &str, url: String) -> Self {
+        Self {
+            name: name.to_string(),
+            category: category.to_string(),
+            url,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Video {
+    // `<show id>:<season>:<episode>`, the id streaming addons expect for an episode
+    pub id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub released: Option<String>,
+    pub season: u32,
+    pub episode: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overview: Option<String>,
+}
@@ -1,8 +1,9 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use manifest::Manifest;
 
 pub mod catalog;
 pub mod manifest;
+pub mod meta;
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
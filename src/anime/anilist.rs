@@ -0,0 +1,232 @@
+use super::{AnimeBatch, AnimeCatalog, AnimeEndpoint, AnimeEntry, AnimeListStatus};
+use crate::{addon::catalog::CatalogType, globals::GlobalClient, mapping::ExternalIds};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const ANILIST_API_URL: &str = "https://graphql.anilist.co";
+// AniList caps perPage at 50
+const ANILIST_PAGE_SIZE: usize = 50;
+
+const MEDIA_FIELDS: &str = "
+    id
+    idMal
+    format
+    seasonYear
+    description(asHtml: false)
+    genres
+    title { romaji english }
+    coverImage { extraLarge large }
+    bannerImage
+";
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct AnilistResponse {
+    data: Option<AnilistData>,
+    errors: Option<Vec<AnilistError>>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct AnilistError {
+    message: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AnilistData {
+    #[serde(rename = "Page")]
+    page: AnilistPage,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AnilistPage {
+    page_info: AnilistPageInfo,
+    media: Option<Vec<AnilistMedia>>,
+    media_list: Option<Vec<AnilistMediaListItem>>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AnilistPageInfo {
+    has_next_page: bool,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct AnilistMediaListItem {
+    media: AnilistMedia,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AnilistMedia {
+    id: u64,
+    id_mal: Option<u64>,
+    format: Option<String>,
+    season_year: Option<i32>,
+    description: Option<String>,
+    genres: Option<Vec<String>>,
+    title: AnilistTitle,
+    cover_image: Option<AnilistCoverImage>,
+    banner_image: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct AnilistTitle {
+    romaji: Option<String>,
+    english: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AnilistCoverImage {
+    extra_large: Option<String>,
+    large: Option<String>,
+}
+
+impl From<AnilistMedia> for AnimeEntry {
+    fn from(media: AnilistMedia) -> Self {
+        AnimeEntry {
+            ids: ExternalIds {
+                anilist: Some(media.id),
+                mal: media.id_mal,
+                ..ExternalIds::default()
+            },
+            title: media
+                .title
+                .english
+                .or(media.title.romaji)
+                .unwrap_or_else(|| media.id.to_string()),
+            poster: media
+                .cover_image
+                .and_then(|cover_image| cover_image.extra_large.or(cover_image.large)),
+            background: media.banner_image,
+            description: media.description,
+            year: media.season_year,
+            genres: media.genres,
+            is_movie: media.format.map(|format| format == "MOVIE"),
+        }
+    }
+}
+
+fn list_status(status: AnimeListStatus) -> &'static str {
+    match status {
+        AnimeListStatus::Watching => "CURRENT",
+        AnimeListStatus::Planning => "PLANNING",
+        AnimeListStatus::Completed => "COMPLETED",
+        AnimeListStatus::Paused => "PAUSED",
+        AnimeListStatus::Dropped => "DROPPED",
+    }
+}
+
+pub(super) async fn fetch_batch(catalog: &AnimeCatalog, batch_index: usize) -> Result<AnimeBatch> {
+    let page = batch_index + 1;
+
+    // Formats are filtered by AniList where the query allows it
+    let (format_in, format_not_in) = match catalog.catalog_type.media_kind() {
+        CatalogType::Movie => (Some(vec!["MOVIE"]), None),
+        CatalogType::Series => (None, Some(vec!["MOVIE", "MUSIC"])),
+    };
+
+    let (query, variables) = match catalog.endpoint {
+        AnimeEndpoint::Seasonal | AnimeEndpoint::Trending => {
+            let (season, year, sort) = match catalog.endpoint {
+                AnimeEndpoint::Seasonal => {
+                    let (season, year) = catalog.season_and_year();
+                    (
+                        Some(season.as_str().to_uppercase()),
+                        Some(year),
+                        "POPULARITY_DESC",
+                    )
+                }
+                _ => (None, None, "TRENDING_DESC"),
+            };
+
+            let query = format!(
+                "query ($page: Int, $perPage: Int, $sort: [MediaSort], $season: MediaSeason, \
+                 $seasonYear: Int, $formatIn: [MediaFormat], $formatNotIn: [MediaFormat]) {{
+                    Page(page: $page, perPage: $perPage) {{
+                        pageInfo {{ hasNextPage }}
+                        media(type: ANIME, isAdult: false, sort: $sort, season: $season,
+                              seasonYear: $seasonYear, format_in: $formatIn,
+                              format_not_in: $formatNotIn) {{ {} }}
+                    }}
+                }}",
+                MEDIA_FIELDS
+            );
+            let variables = json!({
+                "page": page,
+                "perPage": ANILIST_PAGE_SIZE,
+                "sort": [sort],
+                "season": season,
+                "seasonYear": year,
+                "formatIn": format_in,
+                "formatNotIn": format_not_in,
+            });
+            (query, variables)
+        }
+        AnimeEndpoint::UserList => {
+            let query = format!(
+                "query ($page: Int, $perPage: Int, $user: String, $status: MediaListStatus) {{
+                    Page(page: $page, perPage: $perPage) {{
+                        pageInfo {{ hasNextPage }}
+                        mediaList(userName: $user, type: ANIME, status: $status,
+                                  sort: UPDATED_TIME_DESC) {{ media {{ {} }} }}
+                    }}
+                }}",
+                MEDIA_FIELDS
+            );
+            let variables = json!({
+                "page": page,
+                "perPage": ANILIST_PAGE_SIZE,
+                "user": catalog.user()?,
+                "status": list_status(catalog.list_status.unwrap_or_default()),
+            });
+            (query, variables)
+        }
+    };
+
+    let page = query_page(&query, variables).await?;
+
+    let media = match (page.media, page.media_list) {
+        (Some(media), _) => media,
+        (None, Some(media_list)) => media_list.into_iter().map(|item| item.media).collect(),
+        (None, None) => Vec::new(),
+    };
+
+    Ok(AnimeBatch {
+        entries: media.into_iter().map(AnimeEntry::from).collect(),
+        has_more: page.page_info.has_next_page,
+    })
+}
+
+async fn query_page(query: &str, variables: Value) -> Result<AnilistPage> {
+    let client = GlobalClient::get()?;
+
+    let response: AnilistResponse = client
+        .post(ANILIST_API_URL)
+        .json(&json!({ "query": query, "variables": variables }))
+        .send()
+        .await?
+        .json()
+        .await
+        .context("Unable to parse AniList response")?;
+
+    if let Some(error) = response.errors.and_then(|errors| errors.into_iter().next()) {
+        return Err(anyhow!("AniList returned an error: {}", error.message));
+    }
+
+    response
+        .data
+        .map(|data| data.page)
+        .context("AniList response has no data")
+}
@@ -0,0 +1,304 @@
+use super::{AnimeBatch, AnimeCatalog, AnimeEndpoint, AnimeEntry, AnimeListStatus};
+use crate::{addon::catalog::CatalogType, globals::GlobalClient, mapping::ExternalIds};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+const KITSU_API_URL: &str = "https://kitsu.app/api/edge";
+// Kitsu caps page[limit] at 20
+const KITSU_PAGE_SIZE: usize = 20;
+
+// MAL ids never change their Kitsu mapping, so lookups are kept for the life of the process
+static KITSU_IDS: Lazy<Mutex<HashMap<u64, Option<u64>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct KitsuDocument<T> {
+    data: Vec<T>,
+    #[serde(default = "Vec::new")]
+    included: Vec<KitsuAnime>,
+    links: Option<KitsuLinks>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct KitsuLinks {
+    next: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct KitsuAnime {
+    id: String,
+    #[serde(rename = "type")]
+    resource_type: String,
+    attributes: KitsuAnimeAttributes,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct KitsuAnimeAttributes {
+    canonical_title: Option<String>,
+    synopsis: Option<String>,
+    start_date: Option<String>,
+    subtype: Option<String>,
+    poster_image: Option<KitsuImage>,
+    cover_image: Option<KitsuImage>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct KitsuImage {
+    large: Option<String>,
+    original: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct KitsuResource {
+    id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct KitsuLibraryEntry {
+    relationships: KitsuLibraryRelationships,
+}
+
+#[derive(Deserialize, Debug)]
+struct KitsuLibraryRelationships {
+    anime: KitsuRelationship,
+}
+
+#[derive(Deserialize, Debug)]
+struct KitsuRelationship {
+    data: Option<KitsuResource>,
+}
+
+impl From<KitsuAnime> for AnimeEntry {
+    fn from(anime: KitsuAnime) -> Self {
+        let attributes = anime.attributes;
+        AnimeEntry {
+            ids: ExternalIds {
+                kitsu: anime.id.parse().ok(),
+                ..ExternalIds::default()
+            },
+            title: attributes
+                .canonical_title
+                .unwrap_or_else(|| anime.id.clone()),
+            poster: attributes
+                .poster_image
+                .and_then(|image| image.large.or(image.original)),
+            background: attributes
+                .cover_image
+                .and_then(|image| image.original.or(image.large)),
+            description: attributes.synopsis,
+            year: attributes
+                .start_date
+                .and_then(|date| date.get(..4).and_then(|year| year.parse().ok())),
+            genres: None,
+            is_movie: attributes.subtype.map(|subtype| subtype == "movie"),
+        }
+    }
+}
+
+fn list_status(status: AnimeListStatus) -> &'static str {
+    match status {
+        AnimeListStatus::Watching => "current",
+        AnimeListStatus::Planning => "planned",
+        AnimeListStatus::Completed => "completed",
+        AnimeListStatus::Paused => "on_hold",
+        AnimeListStatus::Dropped => "dropped",
+    }
+}
+
+pub(super) async fn fetch_batch(catalog: &AnimeCatalog, batch_index: usize) -> Result<AnimeBatch> {
+    let offset = (batch_index * KITSU_PAGE_SIZE).to_string();
+    let limit = KITSU_PAGE_SIZE.to_string();
+    let subtype = match catalog.catalog_type.media_kind() {
+        CatalogType::Movie => "movie",
+        CatalogType::Series => "TV,ONA,OVA,special",
+    };
+
+    match catalog.endpoint {
+        AnimeEndpoint::Trending => {
+            // Trending is a single short list without paging
+            if batch_index > 0 {
+                return Ok(AnimeBatch {
+                    entries: Vec::new(),
+                    has_more: false,
+                });
+            }
+
+            let document: KitsuDocument<KitsuAnime> =
+                get_json(&["trending", "anime"], &[("limit", &limit)]).await?;
+            Ok(AnimeBatch {
+                entries: document.data.into_iter().map(AnimeEntry::from).collect(),
+                has_more: false,
+            })
+        }
+        AnimeEndpoint::Seasonal => {
+            let (season, year) = catalog.season_and_year();
+            let document: KitsuDocument<KitsuAnime> = get_json(
+                &["anime"],
+                &[
+                    ("filter[season]", season.as_str()),
+                    ("filter[seasonYear]", &year.to_string()),
+                    ("filter[subtype]", subtype),
+                    ("sort", "-userCount"),
+                    ("page[limit]", &limit),
+                    ("page[offset]", &offset),
+                ],
+            )
+            .await?;
+            Ok(batch(document))
+        }
+        AnimeEndpoint::UserList => {
+            let user_id = user_id(catalog.user()?).await?;
+            let document: KitsuDocument<KitsuLibraryEntry> = get_json(
+                &["library-entries"],
+                &[
+                    ("filter[userId]", &user_id),
+                    ("filter[kind]", "anime"),
+                    (
+                        "filter[status]",
+                        list_status(catalog.list_status.unwrap_or_default()),
+                    ),
+                    ("include", "anime"),
+                    ("sort", "-updatedAt"),
+                    ("page[limit]", &limit),
+                    ("page[offset]", &offset),
+                ],
+            )
+            .await?;
+            Ok(library_batch(document))
+        }
+    }
+}
+
+// The anime themselves come back in `included`, in no particular order, so they are picked out
+// by the library entries to keep the most recently updated first
+fn library_batch(document: KitsuDocument<KitsuLibraryEntry>) -> AnimeBatch {
+    let mut anime_by_id: HashMap<String, KitsuAnime> = document
+        .included
+        .into_iter()
+        .filter(|anime| anime.resource_type == "anime")
+        .map(|anime| (anime.id.clone(), anime))
+        .collect();
+
+    AnimeBatch {
+        has_more: document.links.is_some_and(|links| links.next.is_some()),
+        entries: document
+            .data
+            .into_iter()
+            .filter_map(|entry| entry.relationships.anime.data)
+            .filter_map(|anime| anime_by_id.remove(&anime.id))
+            .map(AnimeEntry::from)
+            .collect(),
+    }
+}
+
+fn batch(document: KitsuDocument<KitsuAnime>) -> AnimeBatch {
+    AnimeBatch {
+        has_more: document.links.is_some_and(|links| links.next.is_some()),
+        entries: document.data.into_iter().map(AnimeEntry::from).collect(),
+    }
+}
+
+async fn user_id(user: &str) -> Result<String> {
+    let document: KitsuDocument<KitsuResource> =
+        get_json(&["users"], &[("filter[slug]", user)]).await?;
+    document
+        .data
+        .into_iter()
+        .next()
+        .map(|user| user.id)
+        .with_context(|| format!("No Kitsu user found for {}", user))
+}
+
+/// Looks up the Kitsu id of a MyAnimeList title, for titles missing from the mapping file
+pub(super) async fn kitsu_id_for_mal(mal_id: u64) -> Result<Option<u64>> {
+    if let Some(kitsu_id) = KITSU_IDS.lock().await.get(&mal_id) {
+        return Ok(*kitsu_id);
+    }
+
+    let document: KitsuDocument<KitsuResource> = get_json(
+        &["mappings"],
+        &[
+            ("filter[externalSite]", "myanimelist/anime"),
+            ("filter[externalId]", &mal_id.to_string()),
+            ("include", "item"),
+        ],
+    )
+    .await?;
+
+    let kitsu_id = document
+        .included
+        .iter()
+        .find(|item| item.resource_type == "anime")
+        .and_then(|anime| anime.id.parse().ok());
+
+    KITSU_IDS.lock().await.insert(mal_id, kitsu_id);
+    Ok(kitsu_id)
+}
+
+async fn get_json<T: DeserializeOwned>(path: &[&str], query: &[(&str, &str)]) -> Result<T> {
+    let mut url = Url::parse(KITSU_API_URL)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Kitsu API URL can't have path segments"))?
+        .extend(path);
+    url.query_pairs_mut().extend_pairs(query);
+
+    let client = GlobalClient::get()?;
+    client
+        .get(url)
+        .header("Accept", "application/vnd.api+json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Unable to parse Kitsu response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn library_entries_keep_their_order() {
+        let anime = |id: &str, title: &str| {
+            json!({
+                "id": id,
+                "type": "anime",
+                "attributes": { "canonicalTitle": title }
+            })
+        };
+        let entry = |anime_id: &str| {
+            json!({
+                "id": format!("entry-{}", anime_id),
+                "relationships": { "anime": { "data": { "type": "anime", "id": anime_id } } }
+            })
+        };
+        let document: KitsuDocument<KitsuLibraryEntry> = serde_json::from_value(json!({
+            "data": [entry("2"), entry("3"), entry("1")],
+            "included": [anime("1", "First"), anime("2", "Second"), anime("3", "Third")],
+            "links": { "next": "https://kitsu.app/api/edge/library-entries?page[offset]=20" }
+        }))
+        .unwrap();
+
+        let batch = library_batch(document);
+        let titles: Vec<&str> = batch
+            .entries
+            .iter()
+            .map(|entry| entry.title.as_str())
+            .collect();
+
+        assert_eq!(titles, ["Second", "Third", "First"]);
+        assert!(batch.has_more);
+    }
+}
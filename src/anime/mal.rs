@@ -0,0 +1,181 @@
+use super::{AnimeBatch, AnimeCatalog, AnimeEndpoint, AnimeEntry, AnimeListStatus};
+use crate::{
+    globals::{Environment, GlobalClient},
+    mapping::ExternalIds,
+};
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use serde::Deserialize;
+
+const MAL_API_URL: &str = "https://api.myanimelist.net/v2";
+const MAL_PAGE_SIZE: usize = 100;
+const MAL_FIELDS: &str = "synopsis,genres,start_season,media_type,alternative_titles";
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalResponse {
+    data: Vec<MalItem>,
+    paging: Option<MalPaging>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalPaging {
+    next: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalItem {
+    node: MalAnime,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalAnime {
+    id: u64,
+    title: String,
+    main_picture: Option<MalPicture>,
+    synopsis: Option<String>,
+    genres: Option<Vec<MalGenre>>,
+    start_season: Option<MalSeason>,
+    media_type: Option<String>,
+    alternative_titles: Option<MalAlternativeTitles>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalPicture {
+    medium: Option<String>,
+    large: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalGenre {
+    name: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalSeason {
+    year: i32,
+    season: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+struct MalAlternativeTitles {
+    en: Option<String>,
+}
+
+impl From<MalAnime> for AnimeEntry {
+    fn from(anime: MalAnime) -> Self {
+        let english_title = anime
+            .alternative_titles
+            .and_then(|titles| titles.en)
+            .filter(|title| !title.is_empty());
+
+        AnimeEntry {
+            ids: ExternalIds {
+                mal: Some(anime.id),
+                ..ExternalIds::default()
+            },
+            title: english_title.unwrap_or(anime.title),
+            poster: anime
+                .main_picture
+                .and_then(|picture| picture.large.or(picture.medium)),
+            background: None,
+            description: anime.synopsis,
+            year: anime.start_season.map(|season| season.year),
+            genres: anime
+                .genres
+                .map(|genres| genres.into_iter().map(|genre| genre.name).collect()),
+            is_movie: anime.media_type.map(|media_type| media_type == "movie"),
+        }
+    }
+}
+
+fn list_status(status: AnimeListStatus) -> &'static str {
+    match status {
+        AnimeListStatus::Watching => "watching",
+        AnimeListStatus::Planning => "plan_to_watch",
+        AnimeListStatus::Completed => "completed",
+        AnimeListStatus::Paused => "on_hold",
+        AnimeListStatus::Dropped => "dropped",
+    }
+}
+
+fn client_id(config_client_id: &Option<String>) -> Result<String> {
+    if let Some(client_id) = config_client_id {
+        return Ok(client_id.clone());
+    }
+
+    let env = Environment::get().context("Unable to get global Environment for MAL")?;
+    env.mal_client_id.clone().context(
+        "MyAnimeList requires a mal_client_id in the config or MAL_CLIENT_ID on the server",
+    )
+}
+
+pub(super) async fn fetch_batch(catalog: &AnimeCatalog, batch_index: usize) -> Result<AnimeBatch> {
+    let mut url = Url::parse(MAL_API_URL)?;
+    let mut query = vec![
+        ("limit", MAL_PAGE_SIZE.to_string()),
+        ("offset", (batch_index * MAL_PAGE_SIZE).to_string()),
+        ("fields", MAL_FIELDS.to_string()),
+    ];
+
+    let path = match catalog.endpoint {
+        AnimeEndpoint::Seasonal => {
+            let (season, year) = catalog.season_and_year();
+            query.push(("sort", "anime_num_list_users".to_string()));
+            vec![
+                "anime".to_string(),
+                "season".to_string(),
+                year.to_string(),
+                season.as_str().to_string(),
+            ]
+        }
+        AnimeEndpoint::Trending => {
+            query.push(("ranking_type", "airing".to_string()));
+            vec!["anime".to_string(), "ranking".to_string()]
+        }
+        AnimeEndpoint::UserList => {
+            query.push((
+                "status",
+                list_status(catalog.list_status.unwrap_or_default()).to_string(),
+            ));
+            query.push(("sort", "list_updated_at".to_string()));
+            vec![
+                "users".to_string(),
+                catalog.user()?.to_string(),
+                "animelist".to_string(),
+            ]
+        }
+    };
+
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("MAL API URL can't have path segments"))?
+        .extend(&path);
+    url.query_pairs_mut().extend_pairs(&query);
+
+    let client = GlobalClient::get()?;
+    let response: MalResponse = client
+        .get(url)
+        .header("X-MAL-CLIENT-ID", client_id(&catalog.mal_client_id)?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Unable to parse MyAnimeList response")?;
+
+    Ok(AnimeBatch {
+        has_more: response.paging.is_some_and(|paging| paging.next.is_some()),
+        entries: response
+            .data
+            .into_iter()
+            .map(|item| AnimeEntry::from(item.node))
+            .collect(),
+    })
+}
@@ -0,0 +1,307 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    mapping::{ExternalIds, IdMapping},
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+
+mod anilist;
+mod kitsu;
+mod mal;
+
+// Upper bound on provider pages walked for one of our pages, movies are sparse in most lists
+const MAX_ANIME_BATCHES: usize = 25;
+// How long a provider page is reused. Every page of ours walks the provider from its start, so
+// without this each page Stremio asks for refetches all the batches before it.
+const BATCH_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+// Titles looked up on Kitsu at once
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+
+type BatchCache = HashMap<(String, usize), (Instant, Arc<AnimeBatch>)>;
+
+// Keyed by the catalog config and the batch index
+static BATCHES: Lazy<Mutex<BatchCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeCatalog {
+    provider: AnimeProvider,
+    endpoint: AnimeEndpoint,
+    catalog_type: ContentType,
+    // Seasonal defaults to the season currently airing
+    season: Option<AnimeSeason>,
+    year: Option<i32>,
+    // User name on the provider, for UserList
+    user: Option<String>,
+    list_status: Option<AnimeListStatus>,
+    // Overrides the server's MAL_CLIENT_ID
+    mal_client_id: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum AnimeProvider {
+    Anilist,
+    Kitsu,
+    Mal,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum AnimeEndpoint {
+    Seasonal,
+    Trending,
+    UserList,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimeSeason {
+    Winter,
+    Spring,
+    Summer,
+    Fall,
+}
+
+impl AnimeSeason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AnimeSeason::Winter => "winter",
+            AnimeSeason::Spring => "spring",
+            AnimeSeason::Summer => "summer",
+            AnimeSeason::Fall => "fall",
+        }
+    }
+
+    // Season and year airing right now, going by UTC
+    fn current() -> (Self, i32) {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() / 86_400)
+            .unwrap_or(0) as i64;
+        let (year, month) = year_and_month(days);
+
+        let season = match month {
+            1..=3 => AnimeSeason::Winter,
+            4..=6 => AnimeSeason::Spring,
+            7..=9 => AnimeSeason::Summer,
+            _ => AnimeSeason::Fall,
+        };
+        (season, year)
+    }
+}
+
+// Civil date from days since the unix epoch (Howard Hinnant's algorithm)
+fn year_and_month(days: i64) -> (i32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year as i32, month as u32)
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimeListStatus {
+    #[default]
+    Watching,
+    Planning,
+    Completed,
+    Paused,
+    Dropped,
+}
+
+/// A title as reported by any of the providers
+#[derive(Debug, Default, Clone)]
+struct AnimeEntry {
+    ids: ExternalIds,
+    title: String,
+    poster: Option<String>,
+    background: Option<String>,
+    description: Option<String>,
+    year: Option<i32>,
+    genres: Option<Vec<String>>,
+    // None when the provider didn't say
+    is_movie: Option<bool>,
+}
+
+/// One provider page, `has_more` is false once the provider runs out
+struct AnimeBatch {
+    entries: Vec<AnimeEntry>,
+    has_more: bool,
+}
+
+impl AnimeCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let anime_catalog: AnimeCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = anime_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from anime catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert anime CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let offset = ((pagination.page - 1) * pagination.page_size) as usize;
+        let wanted = offset + pagination.page_size as usize;
+
+        // Providers mix movies and series, so batches are walked until enough of the right
+        // type have been seen
+        let config = serde_json::to_string(self)?;
+        let mut entries = Vec::new();
+        for batch_index in 0..MAX_ANIME_BATCHES {
+            let batch = self.cached_batch(&config, batch_index).await?;
+            entries.extend(
+                batch
+                    .entries
+                    .iter()
+                    .filter(|entry| self.matches_catalog_type(entry))
+                    .cloned(),
+            );
+
+            if !batch.has_more || entries.len() >= wanted {
+                break;
+            }
+        }
+
+        let page_entries: Vec<AnimeEntry> = entries
+            .into_iter()
+            .skip(offset)
+            .take(pagination.page_size as usize)
+            .collect();
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = self.catalog_metas(page_entries).await?;
+        Ok(catalog_response)
+    }
+
+    fn matches_catalog_type(&self, entry: &AnimeEntry) -> bool {
+        match entry.is_movie {
+            Some(is_movie) => is_movie == (self.catalog_type.media_kind() == CatalogType::Movie),
+            None => true,
+        }
+    }
+
+    async fn cached_batch(&self, config: &str, batch_index: usize) -> Result<Arc<AnimeBatch>> {
+        let cache_key = (config.to_string(), batch_index);
+
+        if let Some((fetched_at, batch)) = BATCHES.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < BATCH_CACHE_TTL {
+                return Ok(batch.clone());
+            }
+        }
+
+        let batch = Arc::new(self.fetch_batch(batch_index).await?);
+
+        let mut batches = BATCHES.lock().await;
+        // Expired batches are dropped as new ones come in so the cache can't grow without bound
+        batches.retain(|_, (fetched_at, _)| fetched_at.elapsed() < BATCH_CACHE_TTL);
+        batches.insert(cache_key, (Instant::now(), batch.clone()));
+
+        Ok(batch)
+    }
+
+    async fn fetch_batch(&self, batch_index: usize) -> Result<AnimeBatch> {
+        match self.provider {
+            AnimeProvider::Anilist => anilist::fetch_batch(self, batch_index).await,
+            AnimeProvider::Kitsu => kitsu::fetch_batch(self, batch_index).await,
+            AnimeProvider::Mal => mal::fetch_batch(self, batch_index).await,
+        }
+    }
+
+    fn season_and_year(&self) -> (AnimeSeason, i32) {
+        let (current_season, current_year) = AnimeSeason::current();
+        (
+            self.season.unwrap_or(current_season),
+            self.year.unwrap_or(current_year),
+        )
+    }
+
+    fn user(&self) -> Result<&str> {
+        self.user
+            .as_deref()
+            .context("Anime UserList requires a user")
+    }
+
+    async fn catalog_metas(&self, entries: Vec<AnimeEntry>) -> Result<Vec<CatalogMeta>> {
+        let mapping = IdMapping::get()?;
+
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_LOOKUPS));
+        let mut lookups = JoinSet::new();
+        for (position, entry) in entries.into_iter().enumerate() {
+            let stremio_id = mapping.stremio_id(None, &entry.ids);
+            let permits = permits.clone();
+            lookups.spawn(async move {
+                // Titles missing from the mapping file can still be found on Kitsu by MAL id
+                let stremio_id = match (stremio_id, entry.ids.mal) {
+                    (Some(stremio_id), _) => Ok(Some(stremio_id)),
+                    (None, Some(mal_id)) => {
+                        let _permit = permits.acquire().await;
+                        kitsu::kitsu_id_for_mal(mal_id)
+                            .await
+                            .map(|kitsu_id| kitsu_id.map(|kitsu_id| format!("kitsu:{}", kitsu_id)))
+                    }
+                    (None, None) => Ok(None),
+                };
+                (position, entry, stremio_id)
+            });
+        }
+
+        let mut resolved = Vec::new();
+        while let Some(lookup) = lookups.join_next().await {
+            let (position, entry, stremio_id) = lookup?;
+            match stremio_id {
+                Ok(Some(stremio_id)) => resolved.push((
+                    position,
+                    catalog_meta(entry, stremio_id, self.catalog_type.clone()),
+                )),
+                Ok(None) => {}
+                Err(e) => println!("Unable to resolve an id for {}: {}", entry.title, e),
+            }
+        }
+
+        resolved.sort_by_key(|(position, _)| *position);
+        Ok(resolved.into_iter().map(|(_, meta)| meta).collect())
+    }
+}
+
+fn catalog_meta(entry: AnimeEntry, id: String, catalog_type: ContentType) -> CatalogMeta {
+    let meta = CatalogMeta::from_id(id, entry.title, catalog_type);
+
+    // Metahub only covers IMDb ids, the provider's artwork fills in for kitsu: ids
+    CatalogMeta {
+        poster: meta.poster.clone().or(entry.poster),
+        background: meta.background.clone().or(entry.background),
+        description: entry.description,
+        release_info: entry.year.map(|year| year.to_string()),
+        genres: entry.genres,
+        ..meta
+    }
+}
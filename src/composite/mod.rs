@@ -0,0 +1,305 @@
+use crate::{
+    addon::catalog::{
+        decode_config, encode_path_value, CatalogRequestParams, CatalogSource, PaginationDetails,
+        PAGE_SIZE,
+    },
+    anime::AnimeCatalog,
+    genres,
+    imdb::ImdbCatalog,
+    jellyfin::JellyfinCatalog,
+    letterboxd::LetterboxdCatalog,
+    mdblist::MdblistCatalog,
+    plex::PlexCatalog,
+    rss::RssCatalog,
+    simkl::SimklCatalog,
+    static_list::StaticCatalog,
+    stremio::StremioCatalog,
+    tmdb::TmdbCatalog,
+    trakt::TraktCatalog,
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// Upper bound on pages pulled from any one child for a single request
+const MAX_CHILD_PAGES: i32 = 20;
+// How long a child page is reused. Merged and filtered catalogs are walked from their start on
+// every request, so without this each page Stremio asks for refetches all the ones before it.
+const CHILD_PAGE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+type ChildPageCache = HashMap<String, (Instant, Arc<Vec<Value>>)>;
+
+// Keyed by the child catalog path, e.g. `<config>-trakt/skip=100.json`
+static CHILD_PAGES: Lazy<Mutex<ChildPageCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompositeCatalog {
+    strategy: CompositeStrategy,
+    // Catalog ids of the children, `<base64 config>-<source>` like any catalog in the manifest
+    children: Vec<String>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompositeStrategy {
+    // Every child in turn, in full
+    Concatenate,
+    // One item from each child in turn
+    Interleave,
+    // Concatenate, keeping only the first occurrence of an id
+    Union,
+    // Items of the first child that every other child also has
+    Intersection,
+    // Items of the first child that none of the other children have
+    Difference,
+}
+
+/// A child catalog read one item at a time, pages are fetched as they run out
+pub struct ChildStream<'a> {
+    catalog_id: &'a str,
+    genre: Option<&'a str>,
+    next_page: i32,
+    buffer: VecDeque<Value>,
+    exhausted: bool,
+}
+
+impl<'a> ChildStream<'a> {
+    pub fn new(catalog_id: &'a str, genre: Option<&'a str>) -> Self {
+        Self {
+            catalog_id,
+            genre,
+            next_page: 1,
+            buffer: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.exhausted && self.buffer.is_empty()
+    }
+
+    pub async fn next(&mut self) -> Result<Option<Value>> {
+        while self.buffer.is_empty() && !self.exhausted {
+            if self.next_page > MAX_CHILD_PAGES {
+                self.exhausted = true;
+                break;
+            }
+
+            let metas = self.fetch_page(self.next_page).await?;
+            self.next_page += 1;
+            // Pages can come back short when ids fail to resolve, only an empty one ends a child
+            self.exhausted = metas.is_empty();
+            self.buffer.extend(metas);
+        }
+
+        Ok(self.buffer.pop_front())
+    }
+
+    async fn fetch_page(&self, page: i32) -> Result<Vec<Value>> {
+        // Children are asked for whole pages, exactly as Stremio would ask them, encoded the same
+        // way so a genre like "Action & Adventure" survives the path being split
+        let skip = (page - 1) * PAGE_SIZE;
+        let mut catalog_path = format!("{}/skip={}", encode_path_value(self.catalog_id), skip);
+        if let Some(genre) = self.genre {
+            catalog_path.push_str(&format!("&genre={}", encode_path_value(genre)));
+        }
+        catalog_path.push_str(".json");
+
+        if let Some((fetched_at, metas)) = CHILD_PAGES.lock().await.get(&catalog_path) {
+            if fetched_at.elapsed() < CHILD_PAGE_CACHE_TTL {
+                return Ok(metas.as_ref().clone());
+            }
+        }
+
+        let params = CatalogRequestParams::from_path(&catalog_path)
+            .with_context(|| format!("Invalid child catalog id {}", self.catalog_id))?;
+
+        // Boxed, genre filtering reads its catalog through a stream of its own
+        let response = Box::pin(build_child_catalog(&params)).await?;
+        let metas = match response.get("metas") {
+            Some(Value::Array(metas)) => metas.clone(),
+            _ => return Err(anyhow!("Child catalog response has no metas")),
+        };
+
+        let mut child_pages = CHILD_PAGES.lock().await;
+        // Expired pages are dropped as new ones come in so the cache can't grow without bound
+        child_pages.retain(|_, (fetched_at, _)| fetched_at.elapsed() < CHILD_PAGE_CACHE_TTL);
+        child_pages.insert(catalog_path, (Instant::now(), Arc::new(metas.clone())));
+
+        Ok(metas)
+    }
+
+    async fn collect_ids(&mut self) -> Result<HashSet<String>> {
+        let mut ids = HashSet::new();
+        while let Some(meta) = self.next().await? {
+            if let Some(id) = meta_id(&meta) {
+                ids.insert(id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+impl CompositeCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let composite_catalog: CompositeCatalog =
+            decode_config(&catalog_request_params.catalog_id)?;
+
+        composite_catalog
+            .build(
+                &catalog_request_params.pagination,
+                catalog_request_params.genre.as_deref(),
+            )
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from composite catalog: {}",
+                    e.to_string()
+                )
+            })
+    }
+
+    pub async fn build(
+        &self,
+        pagination: &PaginationDetails,
+        genre: Option<&str>,
+    ) -> Result<Value> {
+        if self.children.is_empty() {
+            return Err(anyhow!("Composite catalog has no children"));
+        }
+
+        // Paging applies to the merged stream, so Stremio's raw skip is used rather than a
+        // page number, and children are walked from their start
+        let skip = pagination.skip.max(0) as usize;
+        let wanted = skip + pagination.page_size as usize;
+
+        let mut children: Vec<ChildStream> = self
+            .children
+            .iter()
+            .map(|catalog_id| ChildStream::new(catalog_id, genre))
+            .collect();
+
+        let metas = match self.strategy {
+            CompositeStrategy::Concatenate => concatenate(&mut children, wanted, false).await?,
+            CompositeStrategy::Union => concatenate(&mut children, wanted, true).await?,
+            CompositeStrategy::Interleave => interleave(&mut children, wanted).await?,
+            CompositeStrategy::Intersection | CompositeStrategy::Difference => {
+                let (first, others) = children
+                    .split_first_mut()
+                    .context("Composite catalog has no children")?;
+
+                let mut other_ids = Vec::new();
+                for other in others {
+                    other_ids.push(other.collect_ids().await?);
+                }
+
+                let keep_shared = matches!(self.strategy, CompositeStrategy::Intersection);
+                let mut metas = Vec::new();
+                while metas.len() < wanted {
+                    let Some(meta) = first.next().await? else {
+                        break;
+                    };
+                    let Some(id) = meta_id(&meta) else {
+                        continue;
+                    };
+
+                    let keep = if keep_shared {
+                        other_ids.iter().all(|ids| ids.contains(&id))
+                    } else {
+                        !other_ids.iter().any(|ids| ids.contains(&id))
+                    };
+                    if keep {
+                        metas.push(meta);
+                    }
+                }
+                metas
+            }
+        };
+
+        let metas: Vec<Value> = metas.into_iter().skip(skip).collect();
+        Ok(json!({ "metas": metas }))
+    }
+}
+
+async fn concatenate(
+    children: &mut [ChildStream<'_>],
+    wanted: usize,
+    dedupe: bool,
+) -> Result<Vec<Value>> {
+    let mut seen = HashSet::new();
+    let mut metas = Vec::new();
+
+    for child in children {
+        while metas.len() < wanted {
+            let Some(meta) = child.next().await? else {
+                break;
+            };
+            if dedupe && !meta_id(&meta).is_some_and(|id| seen.insert(id)) {
+                continue;
+            }
+            metas.push(meta);
+        }
+    }
+
+    Ok(metas)
+}
+
+async fn interleave(children: &mut [ChildStream<'_>], wanted: usize) -> Result<Vec<Value>> {
+    let mut metas = Vec::new();
+
+    while metas.len() < wanted && children.iter().any(|child| !child.is_done()) {
+        for child in children.iter_mut() {
+            if metas.len() >= wanted {
+                break;
+            }
+            if let Some(meta) = child.next().await? {
+                metas.push(meta);
+            }
+        }
+    }
+
+    Ok(metas)
+}
+
+fn meta_id(meta: &Value) -> Option<String> {
+    meta.get("id")?.as_str().map(|id| id.to_string())
+}
+
+/// Builds the response for any catalog, the one place requests are dispatched to their source
+pub async fn build_catalog(params: &CatalogRequestParams) -> Result<Value> {
+    match params.source {
+        CatalogSource::Composite => CompositeCatalog::from_catalog_params(params).await,
+        _ => build_child_catalog(params).await,
+    }
+}
+
+async fn build_child_catalog(params: &CatalogRequestParams) -> Result<Value> {
+    if let Some(genre) = &params.genre {
+        if !genres::filters_natively(params) {
+            return genres::filter_by_genre(params, genre).await;
+        }
+    }
+
+    match params.source {
+        CatalogSource::Trakt => TraktCatalog::from_catalog_params(params).await,
+        CatalogSource::Tmdb => TmdbCatalog::from_catalog_params(params).await,
+        CatalogSource::Mdblist => MdblistCatalog::from_catalog_params(params).await,
+        CatalogSource::Stremio => StremioCatalog::from_catalog_params(params).await,
+        CatalogSource::Letterboxd => LetterboxdCatalog::from_catalog_params(params).await,
+        CatalogSource::Imdb => ImdbCatalog::from_catalog_params(params).await,
+        CatalogSource::Static => StaticCatalog::from_catalog_params(params).await,
+        CatalogSource::Anime => AnimeCatalog::from_catalog_params(params).await,
+        CatalogSource::Simkl => SimklCatalog::from_catalog_params(params).await,
+        CatalogSource::Jellyfin => JellyfinCatalog::from_catalog_params(params).await,
+        CatalogSource::Plex => PlexCatalog::from_catalog_params(params).await,
+        CatalogSource::Rss => RssCatalog::from_catalog_params(params).await,
+        CatalogSource::Composite => Err(anyhow!("Composite catalogs can't be nested")),
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    addon::catalog::{decode_config, Catalog, CatalogRequestParams, CatalogSource},
+    composite::ChildStream,
+    jellyfin::JellyfinCatalog,
+    tmdb, trakt,
+};
+use anyhow::Result;
+use serde_json::{json, Value};
+
+// Offered by sources without a genre list of their own, matched against each item's genres
+const DEFAULT_GENRES: &[&str] = &[
+    "Action",
+    "Adventure",
+    "Animation",
+    "Comedy",
+    "Crime",
+    "Documentary",
+    "Drama",
+    "Family",
+    "Fantasy",
+    "History",
+    "Horror",
+    "Music",
+    "Mystery",
+    "Romance",
+    "Science Fiction",
+    "Thriller",
+    "War",
+    "Western",
+];
+
+// AniList's fixed genre set, MAL names its genres the same way
+const ANIME_GENRES: &[&str] = &[
+    "Action",
+    "Adventure",
+    "Comedy",
+    "Drama",
+    "Ecchi",
+    "Fantasy",
+    "Horror",
+    "Mahou Shoujo",
+    "Mecha",
+    "Music",
+    "Mystery",
+    "Psychological",
+    "Romance",
+    "Sci-Fi",
+    "Slice of Life",
+    "Sports",
+    "Supernatural",
+    "Thriller",
+];
+
+/// Genres a catalog can be filtered by, None when the source has nothing to filter on
+pub async fn genre_options(catalog: &Catalog) -> Result<Option<Vec<String>>> {
+    // Search results come back for a query, there is no list to narrow down
+    if catalog.is_search() {
+        return Ok(None);
+    }
+
+    let Some(config) = catalog.config() else {
+        return Ok(None);
+    };
+    let config_str = |field: &str| config.get(field).and_then(Value::as_str);
+    let static_list =
+        |genres: &[&str]| Some(genres.iter().map(|genre| genre.to_string()).collect());
+
+    let options = match catalog.source() {
+        Some(CatalogSource::Trakt) => Some(
+            trakt::genres(catalog.catalog_type.media_kind())
+                .await?
+                .iter()
+                .map(|genre| genre.name.clone())
+                .collect(),
+        ),
+        // Only Discover can filter on TMDB's side, its other endpoints carry genre ids that
+        // are matched locally. Without a key the catalog itself can't be served either.
+        Some(CatalogSource::Tmdb) => {
+            let Ok(api_key) = tmdb::tmdb_api_key(&config_str("api_key").map(str::to_string)) else {
+                return Ok(None);
+            };
+            Some(
+                tmdb::genres(catalog.catalog_type.media_kind(), &api_key)
+                    .await?
+                    .iter()
+                    .map(|genre| genre.name.clone())
+                    .collect(),
+            )
+        }
+        Some(CatalogSource::Jellyfin) => {
+            let jellyfin_catalog: JellyfinCatalog = serde_json::from_value(config)?;
+            Some(jellyfin_catalog.genre_names().await?.as_ref().clone())
+        }
+        // Kitsu entries come without genres
+        Some(CatalogSource::Anime) => match config_str("provider") {
+            Some("Kitsu") => None,
+            _ => static_list(ANIME_GENRES),
+        },
+        Some(
+            CatalogSource::Imdb
+            | CatalogSource::Stremio
+            | CatalogSource::Simkl
+            | CatalogSource::Plex,
+        ) => static_list(DEFAULT_GENRES),
+        _ => None,
+    };
+
+    Ok(options)
+}
+
+/// Whether a source narrows its own results down to the requested genre. Everything else is
+/// filtered here, see [`filter_by_genre`].
+pub fn filters_natively(catalog_request_params: &CatalogRequestParams) -> bool {
+    match catalog_request_params.source {
+        CatalogSource::Trakt | CatalogSource::Jellyfin | CatalogSource::Composite => true,
+        CatalogSource::Tmdb => decode_config::<Value>(&catalog_request_params.catalog_id)
+            .ok()
+            .and_then(|config| Some(config.get("endpoint")?.as_str()? == "Discover"))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Serves a page of a catalog's items having the genre. The catalog is read from its start
+/// so skip applies to the filtered items rather than to the source's own pages, pages read
+/// for earlier requests come out of the composite page cache rather than the source.
+pub async fn filter_by_genre(
+    catalog_request_params: &CatalogRequestParams,
+    genre: &str,
+) -> Result<Value> {
+    let pagination = &catalog_request_params.pagination;
+    let skip = pagination.skip.max(0) as usize;
+    let wanted = skip + pagination.page_size as usize;
+
+    let catalog_id = format!(
+        "{}-{}",
+        catalog_request_params.catalog_id,
+        catalog_request_params.source.suffix()
+    );
+    let mut stream = ChildStream::new(&catalog_id, None);
+
+    let mut metas = Vec::new();
+    while metas.len() < wanted {
+        let Some(meta) = stream.next().await? else {
+            break;
+        };
+
+        let genres: Vec<String> = meta
+            .get("genres")
+            .and_then(Value::as_array)
+            .map(|genres| {
+                genres
+                    .iter()
+                    .filter_map(|genre| genre.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if has_genre(&genres, genre) {
+            metas.push(meta);
+        }
+    }
+
+    let metas: Vec<Value> = metas.into_iter().skip(skip).collect();
+    Ok(json!({ "metas": metas }))
+}
+
+/// Sources spell genres differently ("Science Fiction", "science-fiction", "sci-fi"), they
+/// are compared by slug
+pub fn slug(genre: &str) -> String {
+    let slug = genre
+        .trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    match slug.as_str() {
+        "sci-fi" | "scifi" => "science-fiction".to_string(),
+        _ => slug,
+    }
+}
+
+pub fn has_genre(genres: &[String], genre: &str) -> bool {
+    let wanted = slug(genre);
+    genres.iter().any(|genre| slug(genre) == wanted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_ignore_case_punctuation_and_spacing() {
+        assert_eq!(slug("Action"), "action");
+        assert_eq!(slug("  Slice of Life "), "slice-of-life");
+        assert_eq!(slug("Action & Adventure"), "action-adventure");
+        assert_eq!(slug("Kids'"), "kids");
+        assert_eq!(slug(""), "");
+    }
+
+    #[test]
+    fn sci_fi_spellings_share_a_slug() {
+        for genre in [
+            "Science Fiction",
+            "science-fiction",
+            "Sci-Fi",
+            "SciFi",
+            "sci fi",
+        ] {
+            assert_eq!(slug(genre), "science-fiction", "{}", genre);
+        }
+    }
+
+    #[test]
+    fn has_genre_compares_slugs() {
+        let genres = vec!["Science Fiction".to_string(), "Drama".to_string()];
+
+        assert!(has_genre(&genres, "sci-fi"));
+        assert!(has_genre(&genres, "DRAMA"));
+        assert!(!has_genre(&genres, "Comedy"));
+        assert!(!has_genre(&[], "Drama"));
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::GlobalClient,
+    uploads::UploadStore,
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+const IMDB_URL: &str = "https://www.imdb.com";
+// IMDb serves a stripped down page to clients without a browser-like user agent
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; DynamicCatalogs)";
+const UPLOAD_KIND: &str = "imdb";
+// Upper bound on list pages scraped when the CSV export isn't available
+const MAX_IMDB_PAGES: u32 = 20;
+// How long a fetched list is reused before it is fetched again
+const LIST_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+type ImdbListCache = HashMap<String, (Instant, Arc<Vec<ImdbEntry>>)>;
+
+static LISTS: Lazy<Mutex<ImdbListCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImdbCatalog {
+    endpoint: ImdbEndpoint,
+    catalog_type: ContentType,
+    // imdb.com/list/ls.../ or imdb.com/user/ur.../watchlist
+    url: Option<String>,
+    // Id handed back by /imdb/upload for an uploaded CSV export
+    upload_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ImdbEndpoint {
+    List,
+    Watchlist,
+    Csv,
+}
+
+/// A single title from an IMDb list, either scraped or read from a CSV export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImdbEntry {
+    pub id: String,
+    pub title: String,
+    pub title_type: Option<String>,
+    pub year: Option<u32>,
+    pub genres: Option<Vec<String>>,
+}
+
+impl ImdbEntry {
+    // Scraped entries carry no type, those are kept for either catalog type
+    fn matches_catalog_type(&self, catalog_type: CatalogType) -> bool {
+        let Some(title_type) = &self.title_type else {
+            return true;
+        };
+
+        let title_type = title_type.to_lowercase();
+        if title_type.contains("episode") {
+            return false;
+        }
+
+        let is_series = title_type.contains("series");
+        match catalog_type {
+            CatalogType::Movie => !is_series,
+            CatalogType::Series => is_series,
+        }
+    }
+}
+
+impl ImdbCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let imdb_catalog: ImdbCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = imdb_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from IMDb catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert IMDb CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let entries = self.entries().await?;
+
+        let offset = ((pagination.page - 1) * pagination.page_size) as usize;
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = entries
+            .iter()
+            .filter(|entry| entry.matches_catalog_type(self.catalog_type.media_kind()))
+            .skip(offset)
+            .take(pagination.page_size as usize)
+            .map(|entry| CatalogMeta {
+                release_info: entry.year.map(|year| year.to_string()),
+                genres: entry.genres.clone(),
+                ..CatalogMeta::from_id(
+                    entry.id.clone(),
+                    entry.title.clone(),
+                    self.catalog_type.clone(),
+                )
+            })
+            .collect();
+        Ok(catalog_response)
+    }
+
+    async fn entries(&self) -> Result<Arc<Vec<ImdbEntry>>> {
+        if let ImdbEndpoint::Csv = self.endpoint {
+            let upload_id = self
+                .upload_id
+                .as_ref()
+                .context("IMDb CSV requires an upload_id")?;
+            return UploadStore::load(UPLOAD_KIND, upload_id).await;
+        }
+
+        let url = self
+            .url
+            .as_ref()
+            .context("IMDb List and Watchlist require a url")?;
+        let url = Url::parse(url).context("Unable to parse IMDb URL")?;
+        let cache_key = url.to_string();
+
+        if let Some((fetched_at, entries)) = LISTS.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < LIST_CACHE_TTL {
+                return Ok(entries.clone());
+            }
+        }
+
+        let list_id = match self.endpoint {
+            ImdbEndpoint::List => path_id(&url, "ls").context("No ls... list id in IMDb URL")?,
+            ImdbEndpoint::Watchlist => {
+                let user_id = path_id(&url, "ur").context("No ur... user id in IMDb URL")?;
+                watchlist_list_id(&user_id).await?
+            }
+            ImdbEndpoint::Csv => return Err(anyhow!("IMDb CSV requires an upload_id")),
+        };
+
+        let entries = Arc::new(fetch_list(&list_id).await?);
+
+        let mut lists = LISTS.lock().await;
+        // Expired lists are dropped as new ones come in so the cache can't grow without bound
+        lists.retain(|_, (fetched_at, _)| fetched_at.elapsed() < LIST_CACHE_TTL);
+        lists.insert(cache_key, (Instant::now(), entries.clone()));
+
+        Ok(entries)
+    }
+}
+
+/// Parses an IMDb CSV export and stores it, returning the id to reference from a catalog config
+pub async fn upload_csv(csv_export: &str) -> Result<String> {
+    let entries = parse_csv(csv_export)?;
+    if entries.is_empty() {
+        return Err(anyhow!("No titles found in IMDb CSV export"));
+    }
+
+    UploadStore::save(UPLOAD_KIND, &entries).await
+}
+
+fn parse_csv(csv_export: &str) -> Result<Vec<ImdbEntry>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(csv_export.as_bytes());
+
+    let headers = reader
+        .headers()
+        .context("Unable to read IMDb CSV export headers")?
+        .clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+
+    let id_column = column("Const").context("IMDb CSV export has no Const column")?;
+    let title_column = column("Title");
+    let title_type_column = column("Title Type");
+    let year_column = column("Year");
+    let genres_column = column("Genres");
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.context("Unable to read IMDb CSV export")?;
+        let cell = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+        };
+
+        let Some(id) = cell(Some(id_column)).filter(|id| id.starts_with("tt")) else {
+            continue;
+        };
+
+        entries.push(ImdbEntry {
+            id: id.to_string(),
+            title: cell(title_column).unwrap_or(id).to_string(),
+            title_type: cell(title_type_column).map(|title_type| title_type.to_string()),
+            year: cell(year_column).and_then(|year| year.parse().ok()),
+            genres: cell(genres_column).map(|genres| {
+                genres
+                    .split(',')
+                    .map(|genre| genre.trim().to_lowercase())
+                    .collect()
+            }),
+        });
+    }
+
+    Ok(entries)
+}
+
+// Finds the path segment holding an IMDb id with the given prefix, e.g. ls123 or ur456
+fn path_id(url: &Url, prefix: &str) -> Option<String> {
+    url.path_segments()?
+        .find(|segment| {
+            segment.starts_with(prefix)
+                && segment[prefix.len()..].chars().all(|c| c.is_ascii_digit())
+        })
+        .map(|segment| segment.to_string())
+}
+
+async fn fetch_text(url: Url) -> Result<String> {
+    let client = GlobalClient::get()?;
+
+    let response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.text().await?)
+}
+
+// Watchlists are lists under the hood, the page names the list it is backed by
+async fn watchlist_list_id(user_id: &str) -> Result<String> {
+    let url = Url::parse(&format!("{}/user/{}/watchlist", IMDB_URL, user_id))?;
+    let response = fetch_text(url).await?;
+    let document = Html::parse_document(&response);
+
+    let selector = Selector::parse(r#"meta[property="pageId"]"#)
+        .map_err(|e| anyhow!("Failed to parse selector: {:?}", e))?;
+
+    document
+        .select(&selector)
+        .next()
+        .and_then(|element| element.value().attr("content"))
+        .filter(|page_id| page_id.starts_with("ls"))
+        .map(|page_id| page_id.to_string())
+        .context("Unable to find the list backing this IMDb watchlist")
+}
+
+async fn fetch_list(list_id: &str) -> Result<Vec<ImdbEntry>> {
+    // The CSV export has everything, including title types, so it is preferred
+    let export_url = Url::parse(&format!("{}/list/{}/export", IMDB_URL, list_id))?;
+    match fetch_text(export_url)
+        .await
+        .and_then(|text| parse_csv(&text))
+    {
+        Ok(entries) if !entries.is_empty() => return Ok(entries),
+        Ok(_) => println!(
+            "IMDb CSV export for {} was empty, scraping instead",
+            list_id
+        ),
+        Err(e) => println!(
+            "IMDb CSV export for {} failed, scraping instead: {}",
+            list_id, e
+        ),
+    }
+
+    scrape_list(list_id).await
+}
+
+async fn scrape_list(list_id: &str) -> Result<Vec<ImdbEntry>> {
+    let link_selector = Selector::parse(r#"a[href*="/title/tt"]"#)
+        .map_err(|e| anyhow!("Failed to parse selector: {:?}", e))?;
+
+    let mut entries: Vec<ImdbEntry> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for page in 1..=MAX_IMDB_PAGES {
+        let url = Url::parse(&format!("{}/list/{}/?page={}", IMDB_URL, list_id, page))?;
+        let response = fetch_text(url).await?;
+        let document = Html::parse_document(&response);
+        let entries_before = entries.len();
+
+        for link in document.select(&link_selector) {
+            let Some(id) = link.value().attr("href").and_then(title_id) else {
+                continue;
+            };
+            let title = link_title(&link.text().collect::<String>());
+
+            // Poster and title links point at the same title, keep the first with text
+            match positions.get(&id) {
+                Some(position) => {
+                    if entries[*position].title.is_empty() {
+                        entries[*position].title = title;
+                    }
+                }
+                None => {
+                    positions.insert(id.clone(), entries.len());
+                    entries.push(ImdbEntry {
+                        id,
+                        title,
+                        title_type: None,
+                        year: None,
+                        genres: None,
+                    });
+                }
+            }
+        }
+
+        if entries.len() == entries_before {
+            break;
+        }
+    }
+
+    for entry in entries.iter_mut().filter(|entry| entry.title.is_empty()) {
+        entry.title = entry.id.clone();
+    }
+
+    Ok(entries)
+}
+
+// e.g. /title/tt0111161/?ref_=ttls_li_tt
+fn title_id(href: &str) -> Option<String> {
+    let after_title = href.split("/title/").nth(1)?;
+    let id: String = after_title
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+
+    (id.starts_with("tt") && id.len() > 2).then_some(id)
+}
+
+// List titles are shown numbered, e.g. "1. The Shawshank Redemption"
+fn link_title(text: &str) -> String {
+    let text = text.trim();
+    match text.split_once(". ") {
+        Some((number, title)) if number.chars().all(|c| c.is_ascii_digit()) => title.to_string(),
+        _ => text.to_string(),
+    }
+}
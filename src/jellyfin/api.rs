@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinItems {
+    pub items: Vec<JellyfinItem>,
+    pub total_record_count: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinGenres {
+    pub items: Vec<JellyfinGenre>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinGenre {
+    pub name: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct JellyfinItem {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "Type")]
+    pub item_type: Option<String>,
+    pub production_year: Option<u32>,
+    pub overview: Option<String>,
+    pub genres: Option<Vec<String>>,
+    // Keys are provider names as Jellyfin's metadata plugins report them, e.g. Imdb, Tmdb, Tvdb
+    #[serde(default)]
+    pub provider_ids: HashMap<String, String>,
+}
+
+impl JellyfinItem {
+    fn provider_id(&self, provider: &str) -> Option<&str> {
+        self.provider_ids
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(provider))
+            .map(|(_, id)| id.as_str())
+            .filter(|id| !id.is_empty())
+    }
+
+    pub fn imdb_id(&self) -> Option<String> {
+        self.provider_id("imdb").map(|id| id.to_string())
+    }
+
+    pub fn tmdb_id(&self) -> Option<u64> {
+        self.provider_id("tmdb")?.parse().ok()
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::{server_client, validate_server_url},
+    tmdb::{imdb_id_from_provider_ids, tmdb_api_key},
+};
+use anyhow::{anyhow, Context, Result};
+use api::{JellyfinGenres, JellyfinItem, JellyfinItems};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::{sync::Mutex, task::JoinSet};
+
+pub mod api;
+
+// How long a library's genre list is reused, every manifest request asks for it
+const GENRES_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+// Keyed by the genres URL (server, library and item type) and the API key
+type GenresCache = HashMap<(String, String), (Instant, Arc<Vec<String>>)>;
+
+static GENRES: Lazy<Mutex<GenresCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JellyfinCatalog {
+    endpoint: JellyfinEndpoint,
+    catalog_type: ContentType,
+    // Must be public unless the server sets ALLOW_PRIVATE_SERVERS
+    server_url: String,
+    api_key: String,
+    // Id of the library, collection or playlist
+    parent_id: String,
+    // Playlists are per user, older servers need the owner to list them
+    user_id: Option<String>,
+    // Overrides the server's TMDB_API_KEY for items that only have a TMDB id
+    tmdb_api_key: Option<String>,
+    // Genre from Stremio's genre extra, never part of the stored config
+    #[serde(skip)]
+    genre: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum JellyfinEndpoint {
+    Library,
+    Collection,
+    Playlist,
+}
+
+impl JellyfinCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let mut jellyfin_catalog: JellyfinCatalog =
+            decode_config(&catalog_request_params.catalog_id)?;
+        jellyfin_catalog.genre = catalog_request_params.genre.clone();
+
+        let catalog_response = jellyfin_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from Jellyfin catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert Jellyfin CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let offset = (pagination.page - 1) * pagination.page_size;
+        let items = self.fetch_items(offset, pagination.page_size).await?;
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = catalog_metas(
+            items,
+            &self.catalog_type,
+            tmdb_api_key(&self.tmdb_api_key).ok(),
+        )
+        .await?;
+        Ok(catalog_response)
+    }
+
+    // Items and Genres both take the library/collection/playlist and item type
+    fn url(&self, path: &str) -> Result<Url> {
+        let mut url =
+            validate_server_url(&self.server_url).context("Invalid Jellyfin server URL")?;
+
+        let item_type = match self.catalog_type.media_kind() {
+            CatalogType::Movie => "Movie",
+            CatalogType::Series => "Series",
+        };
+
+        url.path_segments_mut()
+            .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+            .pop_if_empty()
+            .push(path);
+
+        url.query_pairs_mut()
+            .append_pair("ParentId", &self.parent_id)
+            .append_pair("IncludeItemTypes", item_type)
+            .append_pair("Recursive", "true");
+
+        if let Some(user_id) = &self.user_id {
+            url.query_pairs_mut().append_pair("UserId", user_id);
+        }
+
+        Ok(url)
+    }
+
+    fn items_url(&self, offset: i32, limit: i32) -> Result<Url> {
+        let mut url = self.url("Items")?;
+
+        url.query_pairs_mut()
+            .append_pair("Fields", "ProviderIds,Overview,Genres")
+            .append_pair("StartIndex", &offset.to_string())
+            .append_pair("Limit", &limit.to_string());
+
+        // Collections and playlists keep the order they were curated in
+        if let JellyfinEndpoint::Library = self.endpoint {
+            url.query_pairs_mut()
+                .append_pair("SortBy", "DateCreated,SortName")
+                .append_pair("SortOrder", "Descending");
+        }
+
+        if let Some(genre) = &self.genre {
+            url.query_pairs_mut().append_pair("Genres", genre);
+        }
+
+        Ok(url)
+    }
+
+    /// Genres of the items in the library, collection or playlist
+    pub async fn genre_names(&self) -> Result<Arc<Vec<String>>> {
+        let url = self.url("Genres")?;
+        let cache_key = (url.to_string(), self.api_key.clone());
+
+        if let Some((fetched_at, genres)) = GENRES.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < GENRES_CACHE_TTL {
+                return Ok(genres.clone());
+            }
+        }
+
+        let client = server_client()?;
+
+        let response = client
+            .get(url)
+            .header("X-Emby-Token", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let genres: JellyfinGenres = response.json().await.map_err(|e| {
+            anyhow!(
+                "Unable to convert Jellyfin genres response to json: {}",
+                e.to_string()
+            )
+        })?;
+
+        let genres: Arc<Vec<String>> =
+            Arc::new(genres.items.into_iter().map(|genre| genre.name).collect());
+
+        let mut cache = GENRES.lock().await;
+        cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < GENRES_CACHE_TTL);
+        cache.insert(cache_key, (Instant::now(), genres.clone()));
+
+        Ok(genres)
+    }
+
+    async fn fetch_items(&self, offset: i32, limit: i32) -> Result<Vec<JellyfinItem>> {
+        let client = server_client()?;
+
+        let response = client
+            .get(self.items_url(offset, limit)?)
+            .header("X-Emby-Token", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let items: JellyfinItems = response.json().await.map_err(|e| {
+            anyhow!(
+                "Unable to convert Jellyfin items response to json: {}",
+                e.to_string()
+            )
+        })?;
+
+        Ok(items.items)
+    }
+}
+
+async fn catalog_metas(
+    items: Vec<JellyfinItem>,
+    catalog_type: &ContentType,
+    api_key: Option<String>,
+) -> Result<Vec<CatalogMeta>> {
+    let media_kind = catalog_type.media_kind();
+    // Spawned all at once, the TMDB fallback bounds how many of its lookups go out together
+    let mut lookups = JoinSet::new();
+
+    for (position, item) in items.into_iter().enumerate() {
+        let api_key = api_key.clone();
+        lookups.spawn(async move {
+            let imdb_id = imdb_id_from_provider_ids(
+                media_kind,
+                item.imdb_id(),
+                item.tmdb_id(),
+                api_key.as_deref(),
+            )
+            .await;
+            (position, item, imdb_id)
+        });
+    }
+
+    let mut resolved = Vec::new();
+    while let Some(lookup) = lookups.join_next().await {
+        let (position, item, imdb_id) = lookup?;
+        match imdb_id {
+            Ok(Some(imdb_id)) => resolved.push((position, item, imdb_id)),
+            Ok(None) => {}
+            Err(e) => println!("Unable to resolve IMDb id for {}: {}", item.name, e),
+        }
+    }
+    resolved.sort_by_key(|(position, _, _)| *position);
+
+    Ok(resolved
+        .into_iter()
+        .map(|(_, item, imdb_id)| CatalogMeta {
+            description: item.overview,
+            genres: item.genres,
+            release_info: item.production_year.map(|year| year.to_string()),
+            ..CatalogMeta::from_id(imdb_id, item.name, catalog_type.clone())
+        })
+        .collect())
+}
@@ -0,0 +1,410 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::{read_capped_body, validate_public_url, GlobalClient, PublicClient},
+    tmdb::{imdb_id_for, search_imdb_id, tmdb_api_key},
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::{Client, Url};
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+
+const LETTERBOXD_URL: &str = "https://letterboxd.com";
+// Letterboxd turns away requests without a browser-like user agent
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; DynamicCatalogs)";
+// Upper bound on list pages scraped for a single list
+const MAX_LETTERBOXD_PAGES: u32 = 20;
+// How long a scraped list is reused before it is fetched again
+const LIST_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+// Film to IMDb id lookups rarely change, but are let go eventually so the cache stays bounded
+const FILM_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+// Film pages scraped or titles searched at once when resolving a page of films
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+// Letterboxd pages and list exports are well under this
+const MAX_RESPONSE_BYTES: usize = 5 * 1024 * 1024;
+
+type FilmListCache = HashMap<String, (Instant, Arc<Vec<LetterboxdFilm>>)>;
+type FilmImdbIdCache = HashMap<String, (Instant, Option<String>)>;
+
+static LISTS: Lazy<Mutex<FilmListCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Keyed by slug, or by name and year for CSV rows without one
+static FILM_IMDB_IDS: Lazy<Mutex<FilmImdbIdCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LetterboxdCatalog {
+    endpoint: LetterboxdEndpoint,
+    user: Option<String>,
+    list_slug: Option<String>,
+    csv_url: Option<String>,
+    // Overrides the server's TMDB_API_KEY for id lookups
+    tmdb_api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum LetterboxdEndpoint {
+    List,
+    Watchlist,
+    // A list or watchlist CSV export hosted somewhere reachable
+    Csv,
+}
+
+#[derive(Debug, Clone)]
+struct LetterboxdFilm {
+    slug: Option<String>,
+    name: String,
+    year: Option<u32>,
+}
+
+impl LetterboxdCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let letterboxd_catalog: LetterboxdCatalog =
+            decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = letterboxd_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from Letterboxd catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert Letterboxd CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let films = self.films().await?;
+
+        // Only the films on the requested page get resolved to IMDb ids
+        let offset = ((pagination.page - 1) * pagination.page_size) as usize;
+        let page_films: Vec<LetterboxdFilm> = films
+            .iter()
+            .skip(offset)
+            .take(pagination.page_size as usize)
+            .cloned()
+            .collect();
+
+        let api_key = tmdb_api_key(&self.tmdb_api_key).ok();
+
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_LOOKUPS));
+        let mut lookups = JoinSet::new();
+        for (position, film) in page_films.into_iter().enumerate() {
+            let api_key = api_key.clone();
+            let permits = permits.clone();
+            lookups.spawn(async move {
+                let _permit = permits.acquire().await;
+                let imdb_id = resolve_imdb_id(&film, api_key.as_deref()).await;
+                (position, film, imdb_id)
+            });
+        }
+
+        let mut resolved = Vec::new();
+        while let Some(lookup) = lookups.join_next().await {
+            let (position, film, imdb_id) = lookup?;
+            match imdb_id {
+                Ok(Some(imdb_id)) => resolved.push((position, film, imdb_id)),
+                Ok(None) => {}
+                Err(e) => println!("Unable to resolve IMDb id for {}: {}", film.name, e),
+            }
+        }
+        resolved.sort_by_key(|(position, _, _)| *position);
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = resolved
+            .into_iter()
+            .map(|(_, film, imdb_id)| CatalogMeta {
+                release_info: film.year.map(|year| year.to_string()),
+                ..CatalogMeta::from_id(imdb_id, film.name, ContentType::Movie)
+            })
+            .collect();
+        Ok(catalog_response)
+    }
+
+    fn cache_key(&self) -> Result<String> {
+        match self.endpoint {
+            LetterboxdEndpoint::List => match (&self.user, &self.list_slug) {
+                (Some(user), Some(list_slug)) => Ok(format!("{}/list/{}", user, list_slug)),
+                _ => Err(anyhow!("Letterboxd List requires a user and a list slug")),
+            },
+            LetterboxdEndpoint::Watchlist => match &self.user {
+                Some(user) => Ok(format!("{}/watchlist", user)),
+                None => Err(anyhow!("Letterboxd Watchlist requires a user")),
+            },
+            LetterboxdEndpoint::Csv => self
+                .csv_url
+                .clone()
+                .context("Letterboxd CSV requires a csv_url"),
+        }
+    }
+
+    async fn films(&self) -> Result<Arc<Vec<LetterboxdFilm>>> {
+        let cache_key = self.cache_key()?;
+
+        if let Some((fetched_at, films)) = LISTS.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < LIST_CACHE_TTL {
+                return Ok(films.clone());
+            }
+        }
+
+        let films = match self.endpoint {
+            LetterboxdEndpoint::List | LetterboxdEndpoint::Watchlist => {
+                scrape_films(&cache_key).await?
+            }
+            LetterboxdEndpoint::Csv => csv_films(&cache_key).await?,
+        };
+        let films = Arc::new(films);
+
+        let mut lists = LISTS.lock().await;
+        // Expired lists are dropped as new ones come in so the cache can't grow without bound
+        lists.retain(|_, (fetched_at, _)| fetched_at.elapsed() < LIST_CACHE_TTL);
+        lists.insert(cache_key, (Instant::now(), films.clone()));
+
+        Ok(films)
+    }
+}
+
+// Letterboxd itself is fetched with the global client, CSV exports from user supplied URLs with
+// the public one
+async fn fetch_text(client: &Client, url: Url) -> Result<String> {
+    let response = client
+        .get(url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let body = read_capped_body(response, MAX_RESPONSE_BYTES).await?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+// `list_path` is `{user}/list/{slug}` or `{user}/watchlist`
+async fn scrape_films(list_path: &str) -> Result<Vec<LetterboxdFilm>> {
+    let poster_selector = Selector::parse("[data-film-slug], [data-item-slug]")
+        .map_err(|e| anyhow!("Failed to parse selector: {:?}", e))?;
+    let next_selector =
+        Selector::parse("a.next").map_err(|e| anyhow!("Failed to parse selector: {:?}", e))?;
+
+    let mut films = Vec::new();
+
+    for page in 1..=MAX_LETTERBOXD_PAGES {
+        let url = Url::parse(&format!("{}/{}/page/{}/", LETTERBOXD_URL, list_path, page))?;
+        let response = fetch_text(GlobalClient::get()?, url).await?;
+        let document = Html::parse_document(&response);
+
+        let page_films: Vec<LetterboxdFilm> = document
+            .select(&poster_selector)
+            .filter_map(film_from_poster)
+            .collect();
+
+        if page_films.is_empty() {
+            break;
+        }
+        films.extend(page_films);
+
+        if document.select(&next_selector).next().is_none() {
+            break;
+        }
+    }
+
+    Ok(films)
+}
+
+fn film_from_poster(element: ElementRef) -> Option<LetterboxdFilm> {
+    let attributes = element.value();
+
+    let slug = attributes
+        .attr("data-film-slug")
+        .or_else(|| attributes.attr("data-item-slug"))?
+        .to_string();
+
+    // Newer markup folds the year into the name, e.g. "Heat (1995)"
+    let full_name = attributes
+        .attr("data-film-name")
+        .or_else(|| attributes.attr("data-item-name"))
+        .unwrap_or(&slug)
+        .to_string();
+
+    let (name, year) = match attributes
+        .attr("data-film-release-year")
+        .and_then(|year| year.parse::<u32>().ok())
+    {
+        Some(year) => (full_name, Some(year)),
+        None => split_name_and_year(&full_name),
+    };
+
+    Some(LetterboxdFilm {
+        slug: Some(slug),
+        name,
+        year,
+    })
+}
+
+fn split_name_and_year(full_name: &str) -> (String, Option<u32>) {
+    if let Some(without_paren) = full_name.strip_suffix(')') {
+        if let Some((name, year)) = without_paren.rsplit_once(" (") {
+            if let Ok(year) = year.parse::<u32>() {
+                return (name.to_string(), Some(year));
+            }
+        }
+    }
+    (full_name.to_string(), None)
+}
+
+async fn csv_films(csv_url: &str) -> Result<Vec<LetterboxdFilm>> {
+    let url = validate_public_url(csv_url)?;
+    let response = fetch_text(PublicClient::get()?, url).await?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(response.as_bytes());
+
+    // List exports start with a block describing the list itself, the films follow the
+    // second header row. Watchlist exports only have the one header.
+    let mut columns: Option<(usize, Option<usize>, Option<usize>)> = None;
+    let mut films = Vec::new();
+
+    for record in reader.records() {
+        let record = record.context("Unable to read Letterboxd CSV export")?;
+        let position = |name: &str| record.iter().position(|cell| cell == name);
+
+        if let (Some(name), Some(year)) = (position("Name"), position("Year")) {
+            let url = position("URL").or_else(|| position("Letterboxd URI"));
+            columns = Some((name, Some(year), url));
+            continue;
+        }
+
+        let Some((name_column, year_column, url_column)) = columns else {
+            continue;
+        };
+
+        let Some(name) = record.get(name_column).filter(|name| !name.is_empty()) else {
+            continue;
+        };
+
+        let year = year_column
+            .and_then(|column| record.get(column))
+            .and_then(|year| year.parse::<u32>().ok());
+
+        // Only full film URLs carry the slug, boxd.it short links do not
+        let slug = url_column
+            .and_then(|column| record.get(column))
+            .and_then(|url| url.split("/film/").nth(1))
+            .map(|slug| slug.trim_end_matches('/').to_string());
+
+        films.push(LetterboxdFilm {
+            slug,
+            name: name.to_string(),
+            year,
+        });
+    }
+
+    Ok(films)
+}
+
+async fn resolve_imdb_id(film: &LetterboxdFilm, api_key: Option<&str>) -> Result<Option<String>> {
+    let cache_key = match (&film.slug, film.year) {
+        (Some(slug), _) => slug.clone(),
+        (None, Some(year)) => format!("{} ({})", film.name, year),
+        (None, None) => film.name.clone(),
+    };
+
+    if let Some((fetched_at, imdb_id)) = FILM_IMDB_IDS.lock().await.get(&cache_key) {
+        if fetched_at.elapsed() < FILM_CACHE_TTL {
+            return Ok(imdb_id.clone());
+        }
+    }
+
+    let imdb_id = match &film.slug {
+        Some(slug) => film_page_imdb_id(slug, api_key).await?,
+        None => match api_key {
+            Some(api_key) => {
+                search_imdb_id(CatalogType::Movie, &film.name, film.year, api_key).await?
+            }
+            None => {
+                return Err(anyhow!(
+                    "No TMDB API key available to look up films by title"
+                ))
+            }
+        },
+    };
+
+    let mut film_imdb_ids = FILM_IMDB_IDS.lock().await;
+    film_imdb_ids.retain(|_, (fetched_at, _)| fetched_at.elapsed() < FILM_CACHE_TTL);
+    film_imdb_ids.insert(cache_key, (Instant::now(), imdb_id.clone()));
+
+    Ok(imdb_id)
+}
+
+// The film page links out to TMDB, and to IMDb which is used when TMDB can't help
+async fn film_page_imdb_id(slug: &str, api_key: Option<&str>) -> Result<Option<String>> {
+    let url = Url::parse(&format!("{}/film/{}/", LETTERBOXD_URL, slug))?;
+    let response = fetch_text(GlobalClient::get()?, url).await?;
+
+    let (tmdb_id, imdb_link_id) = {
+        let document = Html::parse_document(&response);
+        (tmdb_movie_id(&document), imdb_link_id(&document))
+    };
+
+    let mut imdb_id = None;
+    if let (Some(tmdb_id), Some(api_key)) = (tmdb_id, api_key) {
+        imdb_id = imdb_id_for(CatalogType::Movie, tmdb_id, api_key)
+            .await
+            .unwrap_or(None);
+    }
+    Ok(imdb_id.or(imdb_link_id))
+}
+
+fn tmdb_movie_id(document: &Html) -> Option<u64> {
+    let body_selector = Selector::parse("body[data-tmdb-id]").ok()?;
+    if let Some(body) = document.select(&body_selector).next() {
+        if let Some(tmdb_id) = body.value().attr("data-tmdb-id") {
+            return tmdb_id.parse().ok();
+        }
+    }
+
+    // e.g. https://www.themoviedb.org/movie/949/
+    let link_selector =
+        Selector::parse(r#"a[data-track-action="TMDB"], a[data-track-action="TMDb"]"#).ok()?;
+    let href = document
+        .select(&link_selector)
+        .next()?
+        .value()
+        .attr("href")?;
+    href.split("/movie/")
+        .nth(1)?
+        .trim_end_matches('/')
+        .parse()
+        .ok()
+}
+
+fn imdb_link_id(document: &Html) -> Option<String> {
+    // e.g. http://www.imdb.com/title/tt0113277/maindetails
+    let link_selector = Selector::parse(r#"a[data-track-action="IMDb"]"#).ok()?;
+    let href = document
+        .select(&link_selector)
+        .next()?
+        .value()
+        .attr("href")?;
+    href.split("/title/")
+        .nth(1)?
+        .split('/')
+        .next()
+        .filter(|id| id.starts_with("tt"))
+        .map(|id| id.to_string())
+}
@@ -1,21 +1,41 @@
-use addon::catalog::{CatalogRequestParams, CatalogSource, CatalogType};
+use addon::catalog::{CatalogRequestParams, ContentType};
+use addon::manifest::{AddonConfig, Manifest};
 use addon::Addon;
 use anyhow::{Context, Result};
 use axum::extract::{Path, Query};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::{routing::get, Router};
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect};
+use axum::{
+    routing::{get, post},
+    Router,
+};
 use globals::set_globals;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::{ServeDir, ServeFile};
-use trakt::{get_trakt_list_id, TraktCatalog};
+use trakt::auth::{poll_device_token, request_device_code};
+use trakt::lists::{get_trakt_list_id, validate_trakt_url};
 
 mod addon;
+mod anime;
+mod composite;
+mod genres;
 mod globals;
+mod imdb;
+mod jellyfin;
+mod letterboxd;
+mod mapping;
+mod mdblist;
+mod plex;
+mod rss;
+mod simkl;
+mod static_list;
+mod stremio;
+mod tmdb;
 mod trakt;
+mod uploads;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -30,11 +50,20 @@ async fn main() -> Result<()> {
 
     let app = Router::new()
         .route("/health", get(health))
+        .route("/manifest.json", get(unconfigured_manifest))
+        .route("/configure", get(configure))
         .route("/:config/manifest.json", get(manifest))
         .nest_service("/:config/configure", dashboard_service)
         .route("/:config/catalog/:type/*stremio_catalog_path", get(catalog))
+        .route("/:config/meta/:type/:meta_id", get(meta))
         // .route("/example-trakt", get(example_trakt))
         .route("/trakt/extract-list-id", get(trakt_list_id))
+        .route("/trakt/auth/device-code", get(trakt_device_code))
+        .route("/trakt/auth/device-token", get(trakt_device_token))
+        .route("/simkl/auth/device-code", get(simkl_device_code))
+        .route("/simkl/auth/device-token", get(simkl_device_token))
+        .route("/imdb/upload", post(imdb_upload))
+        .route("/static/upload", post(static_upload))
         .layer(cors);
 
     let address = "127.0.0.1:8080";
@@ -69,6 +98,16 @@ async fn health() -> &'static str {
 //     String::from("TODO: REPLACE THIS")
 // }
 
+async fn unconfigured_manifest() -> impl IntoResponse {
+    axum::response::Json(json!(Manifest::unconfigured()))
+}
+
+// The dashboard is served under a config, a new install starts from an empty catalog list and
+// the id it keeps for as long as it is edited through the dashboard
+async fn configure() -> Redirect {
+    Redirect::temporary(&format!("/{}/configure/", AddonConfig::new_install()))
+}
+
 async fn manifest(Path(config): Path<String>) -> Result<impl IntoResponse, (StatusCode, String)> {
     let addon = Addon::build(&config)
         .await
@@ -78,9 +117,31 @@ async fn manifest(Path(config): Path<String>) -> Result<impl IntoResponse, (Stat
     Ok((StatusCode::OK, axum::response::Json(manifest)))
 }
 
-async fn catalog(
-    Path((config, catalog_type, stremio_catalog_path)): Path<(String, CatalogType, String)>,
+async fn meta(
+    Path((_config, content_type, meta_id)): Path<(String, ContentType, String)>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let meta_id = meta_id.strip_suffix(".json").ok_or((
+        StatusCode::BAD_REQUEST,
+        String::from("API expects GET request for JSON file. No valid extension provided."),
+    ))?;
+
+    // Every meta comes from Trakt whichever source the catalog listing the item used
+    let response = trakt::meta::meta(&content_type, meta_id)
+        .await
+        .map_err(|e| {
+            println!("Unable to build meta for {}: {}", meta_id, e);
+            let error_message = format!("Unable to build meta for {}", meta_id);
+            ((StatusCode::BAD_REQUEST), error_message)
+        })?;
+
+    Ok((StatusCode::OK, axum::response::Json(response)))
+}
+
+async fn catalog(uri: Uri) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // Taken from the raw URI as path extractors decode captures, from_path decodes extras only
+    // once they have been split apart. `/:config/catalog/:type/` comes before the catalog path.
+    let stremio_catalog_path = uri.path().splitn(5, '/').nth(4).unwrap_or_default();
+
     // Ensure the path ends with `.json`
     // This will remain true for every request from Stremio
     if !stremio_catalog_path.ends_with(".json") {
@@ -92,43 +153,133 @@ async fn catalog(
 
     // Extract path options provided by Stremio (i.e. genre, pagination)
     // We are storing the entire catalog config in the catalog_id provided by Stremio
-    let catalog_params = CatalogRequestParams::from_path(&stremio_catalog_path).map_err(|e| {
+    let catalog_params = CatalogRequestParams::from_path(stremio_catalog_path).map_err(|e| {
         let error_message = format!("Unable to parse CatalogPathOptions: {}", e);
         ((StatusCode::BAD_REQUEST), error_message)
     })?;
 
-    // Build catalog from parsed params based on query source, each CatalogParams vary by source
-    let response = match catalog_params.source {
-        CatalogSource::Trakt => {
-            let response = TraktCatalog::from_catalog_params(&catalog_params)
-                .await
-                .map_err(|e| {
-                    let error_message = format!(
-                        "Unable to build TraktCatalog with provided Catalog Path: {}",
-                        e
-                    );
-                    ((StatusCode::BAD_REQUEST), error_message)
-                })?;
-
-            (StatusCode::OK, axum::response::Json(response)).into_response()
-        }
-    };
-
-    Ok(response)
+    // Build catalog from parsed params based on query source, each CatalogParams vary by source.
+    // Upstream errors can quote URLs and the credentials in them, they only go to the log.
+    let response = composite::build_catalog(&catalog_params)
+        .await
+        .map_err(|e| {
+            let source = catalog_params.source.suffix();
+            println!("Unable to build {} catalog: {}", source, e);
+            let error_message = format!("Unable to build {} catalog", source);
+            ((StatusCode::BAD_REQUEST), error_message)
+        })?;
+
+    Ok((StatusCode::OK, axum::response::Json(response)))
 }
 
 async fn trakt_list_id(
     Query(params): Query<HashMap<String, String>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let mut list_id = String::new();
-    if let Some(url) = params.get("url") {
-        let scraped_id = get_trakt_list_id(url)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        list_id.push_str(&scraped_id);
-    } else {
-        return Err(StatusCode::NO_CONTENT);
-    }
+) -> Result<impl IntoResponse, (StatusCode, axum::response::Json<Value>)> {
+    let url = params
+        .get("url")
+        .ok_or_else(|| json_error(StatusCode::BAD_REQUEST, "Missing url query parameter"))?;
+
+    let url = validate_trakt_url(url).map_err(|e| {
+        let error_message = format!("Invalid Trakt list URL: {}", e);
+        json_error(StatusCode::BAD_REQUEST, &error_message)
+    })?;
+
+    let list_id = get_trakt_list_id(&url).await.map_err(|e| {
+        let error_message = format!("Unable to resolve Trakt list id: {}", e);
+        json_error(StatusCode::BAD_GATEWAY, &error_message)
+    })?;
+
     let id_json = json!({"id": list_id});
     Ok((StatusCode::OK, axum::response::Json(id_json)))
 }
+
+async fn imdb_upload(
+    csv_export: String,
+) -> Result<impl IntoResponse, (StatusCode, axum::response::Json<Value>)> {
+    let upload_id = imdb::upload_csv(&csv_export).await.map_err(|e| {
+        let error_message = format!("Unable to store IMDb CSV export: {}", e);
+        json_error(StatusCode::BAD_REQUEST, &error_message)
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        axum::response::Json(json!({ "id": upload_id })),
+    ))
+}
+
+async fn static_upload(
+    headers: HeaderMap,
+    body: String,
+) -> Result<impl IntoResponse, (StatusCode, axum::response::Json<Value>)> {
+    // JSON unless the upload says otherwise
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("csv"));
+
+    let list_id = static_list::upload_list(&body, is_csv).await.map_err(|e| {
+        let error_message = format!("Unable to store uploaded list: {}", e);
+        json_error(StatusCode::BAD_REQUEST, &error_message)
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        axum::response::Json(json!({ "id": list_id })),
+    ))
+}
+
+fn json_error(status: StatusCode, message: &str) -> (StatusCode, axum::response::Json<Value>) {
+    (status, axum::response::Json(json!({ "error": message })))
+}
+
+async fn trakt_device_code() -> Result<impl IntoResponse, (StatusCode, String)> {
+    let device_code = request_device_code().await.map_err(|e| {
+        let error_message = format!("Unable to request Trakt device code: {}", e);
+        (StatusCode::BAD_GATEWAY, error_message)
+    })?;
+
+    Ok((StatusCode::OK, axum::response::Json(device_code)))
+}
+
+async fn trakt_device_token(
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let device_code = params.get("device_code").ok_or((
+        StatusCode::BAD_REQUEST,
+        String::from("Missing device_code query parameter"),
+    ))?;
+
+    let status = poll_device_token(device_code).await.map_err(|e| {
+        let error_message = format!("Unable to poll Trakt device token: {}", e);
+        (StatusCode::BAD_GATEWAY, error_message)
+    })?;
+
+    Ok((StatusCode::OK, axum::response::Json(status)))
+}
+
+async fn simkl_device_code() -> Result<impl IntoResponse, (StatusCode, String)> {
+    let device_code = simkl::auth::request_device_code().await.map_err(|e| {
+        let error_message = format!("Unable to request Simkl PIN code: {}", e);
+        (StatusCode::BAD_GATEWAY, error_message)
+    })?;
+
+    Ok((StatusCode::OK, axum::response::Json(device_code)))
+}
+
+async fn simkl_device_token(
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let user_code = params.get("user_code").ok_or((
+        StatusCode::BAD_REQUEST,
+        String::from("Missing user_code query parameter"),
+    ))?;
+
+    let status = simkl::auth::poll_device_token(user_code)
+        .await
+        .map_err(|e| {
+            let error_message = format!("Unable to poll Simkl PIN token: {}", e);
+            (StatusCode::BAD_GATEWAY, error_message)
+        })?;
+
+    Ok((StatusCode::OK, axum::response::Json(status)))
+}
@@ -0,0 +1,141 @@
+use crate::globals::Environment;
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+static ID_MAPPING: OnceCell<IdMapping> = OnceCell::new();
+
+/// Cross-references between anime databases and IMDb/TMDB/TVDB, loaded from the local file at
+/// ANIME_MAPPING_FILE (the anime-lists `anime-list-full.json` format)
+#[derive(Debug, Default)]
+pub struct IdMapping {
+    entries: Vec<MappingEntry>,
+    by_anilist: HashMap<u64, usize>,
+    by_mal: HashMap<u64, usize>,
+    by_kitsu: HashMap<u64, usize>,
+    by_tmdb: HashMap<u64, usize>,
+    by_tvdb: HashMap<u64, usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MappingEntry {
+    #[serde(default, deserialize_with = "loose_id")]
+    pub anilist_id: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub mal_id: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub kitsu_id: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub themoviedb_id: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub thetvdb_id: Option<u64>,
+    pub imdb_id: Option<String>,
+}
+
+/// Whatever ids a source knows about a title
+#[derive(Debug, Default, Clone)]
+pub struct ExternalIds {
+    pub anilist: Option<u64>,
+    pub mal: Option<u64>,
+    pub kitsu: Option<u64>,
+    pub tmdb: Option<u64>,
+    pub tvdb: Option<u64>,
+}
+
+// The mapping file isn't consistent about ids being numbers or strings
+fn loose_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let value = Option::<Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(Value::String(string)) => string.parse().ok(),
+        _ => None,
+    })
+}
+
+impl IdMapping {
+    pub fn set() -> Result<()> {
+        let env = Environment::get().context("Unable to get global Environment for id mapping")?;
+
+        let mapping = match &env.anime_mapping_file {
+            Some(path) => {
+                let contents = std::fs::read(path)
+                    .with_context(|| format!("Unable to read id mapping file at {}", path))?;
+                let entries: Vec<MappingEntry> =
+                    serde_json::from_slice(&contents).context("Unable to parse id mapping file")?;
+                println!("Loaded {} id mappings from {}", entries.len(), path);
+                Self::from_entries(entries)
+            }
+            None => Self::default(),
+        };
+
+        ID_MAPPING
+            .set(mapping)
+            .map_err(|_| anyhow!("Id mapping is already set"))?;
+        Ok(())
+    }
+
+    pub fn get() -> Result<&'static IdMapping> {
+        ID_MAPPING
+            .get()
+            .context("Id mapping cell is empty, or being initialized")
+    }
+
+    fn from_entries(entries: Vec<MappingEntry>) -> Self {
+        let mut mapping = Self::default();
+
+        for (position, entry) in entries.iter().enumerate() {
+            // First entry wins, later ones are usually specials/recaps of the same show
+            let index = |ids: &mut HashMap<u64, usize>, id: Option<u64>| {
+                if let Some(id) = id {
+                    ids.entry(id).or_insert(position);
+                }
+            };
+            index(&mut mapping.by_anilist, entry.anilist_id);
+            index(&mut mapping.by_mal, entry.mal_id);
+            index(&mut mapping.by_kitsu, entry.kitsu_id);
+            index(&mut mapping.by_tmdb, entry.themoviedb_id);
+            index(&mut mapping.by_tvdb, entry.thetvdb_id);
+        }
+
+        mapping.entries = entries;
+        mapping
+    }
+
+    pub fn find(&self, ids: &ExternalIds) -> Option<&MappingEntry> {
+        let lookups = [
+            (&self.by_anilist, ids.anilist),
+            (&self.by_mal, ids.mal),
+            (&self.by_kitsu, ids.kitsu),
+            (&self.by_tmdb, ids.tmdb),
+            (&self.by_tvdb, ids.tvdb),
+        ];
+
+        lookups
+            .into_iter()
+            .find_map(|(by_id, id)| by_id.get(&id?))
+            .map(|position| &self.entries[*position])
+    }
+
+    /// The id Stremio should see for a title: IMDb when known, otherwise `kitsu:` so the
+    /// Kitsu addon can still provide metadata
+    pub fn stremio_id(&self, imdb_id: Option<&str>, ids: &ExternalIds) -> Option<String> {
+        if let Some(imdb_id) = imdb_id.filter(|id| id.starts_with("tt")) {
+            return Some(imdb_id.to_string());
+        }
+
+        let entry = self.find(ids);
+
+        if let Some(imdb_id) = entry
+            .and_then(|entry| entry.imdb_id.as_deref())
+            .filter(|id| id.starts_with("tt"))
+        {
+            return Some(imdb_id.to_string());
+        }
+
+        ids.kitsu
+            .or(entry.and_then(|entry| entry.kitsu_id))
+            .map(|kitsu_id| format!("kitsu:{}", kitsu_id))
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+// The list items endpoint either splits entries by media type or returns one flat array
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum MdblistItems {
+    Split {
+        #[serde(default)]
+        movies: Vec<MdblistItem>,
+        #[serde(default)]
+        shows: Vec<MdblistItem>,
+    },
+    Flat(Vec<MdblistItem>),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct MdblistItem {
+    pub id: Option<u64>,
+    pub rank: Option<u32>,
+    pub title: String,
+    pub imdb_id: Option<String>,
+    pub tvdb_id: Option<u64>,
+    pub mediatype: Option<String>,
+    pub release_year: Option<u32>,
+}
@@ -0,0 +1,113 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::GlobalClient,
+};
+use anyhow::{anyhow, Context, Result};
+use api::{MdblistItem, MdblistItems};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub mod api;
+
+const MDBLIST_API_URL: &str = "https://api.mdblist.com";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MdblistCatalog {
+    // Either a numeric list id or `user/slug`
+    list: String,
+    catalog_type: ContentType,
+    api_key: String,
+}
+
+impl MdblistCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let mdblist_catalog: MdblistCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = mdblist_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from MDBList catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert MDBList CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let client = GlobalClient::get()?;
+
+        let mut url = Url::parse(MDBLIST_API_URL)?;
+        url.path_segments_mut()
+            .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+            .push("lists")
+            .extend(self.list.trim_matches('/').split('/'))
+            .push("items");
+
+        let offset = (pagination.page - 1) * pagination.page_size;
+        url.query_pairs_mut()
+            .append_pair("apikey", &self.api_key)
+            .append_pair("limit", &pagination.page_size.to_string())
+            .append_pair("offset", &offset.to_string());
+
+        // reqwest errors name the URL they failed on, which carries the apikey, so it is dropped
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?
+            .error_for_status()
+            .map_err(reqwest::Error::without_url)?;
+
+        let items: MdblistItems = response.json().await.map_err(|e| {
+            anyhow!(
+                "Unable to convert MDBList API response to json: {}",
+                e.without_url().to_string()
+            )
+        })?;
+
+        let items = match items {
+            MdblistItems::Split { movies, shows } => match self.catalog_type.media_kind() {
+                CatalogType::Movie => movies,
+                CatalogType::Series => shows,
+            },
+            MdblistItems::Flat(items) => items
+                .into_iter()
+                .filter(|item| matches_catalog_type(item, self.catalog_type.media_kind()))
+                .collect(),
+        };
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = items
+            .into_iter()
+            .filter_map(|item| catalog_meta(item, self.catalog_type.clone()))
+            .collect();
+        Ok(catalog_response)
+    }
+}
+
+fn matches_catalog_type(item: &MdblistItem, catalog_type: CatalogType) -> bool {
+    matches!(
+        (item.mediatype.as_deref(), catalog_type),
+        (Some("movie"), CatalogType::Movie) | (Some("show"), CatalogType::Series) | (None, _)
+    )
+}
+
+fn catalog_meta(item: MdblistItem, catalog_type: ContentType) -> Option<CatalogMeta> {
+    let imdb_id = item.imdb_id.filter(|id| id.starts_with("tt"))?;
+
+    Some(CatalogMeta {
+        release_info: item.release_year.map(|year| year.to_string()),
+        ..CatalogMeta::from_id(imdb_id, item.title, catalog_type)
+    })
+}
@@ -0,0 +1,64 @@
+use serde::Deserialize;
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlexResponse {
+    pub media_container: PlexMediaContainer,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexMediaContainer {
+    pub total_size: Option<u32>,
+    #[serde(default, rename = "Metadata")]
+    pub metadata: Vec<PlexMetadata>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexMetadata {
+    pub rating_key: Option<String>,
+    pub title: String,
+    #[serde(rename = "type")]
+    pub metadata_type: Option<String>,
+    pub year: Option<u32>,
+    pub summary: Option<String>,
+    #[serde(default, rename = "Genre")]
+    pub genres: Vec<PlexTag>,
+    // e.g. imdb://tt0111161, tmdb://278, tvdb://81189
+    #[serde(default, rename = "Guid")]
+    pub guids: Vec<PlexGuid>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct PlexTag {
+    pub tag: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct PlexGuid {
+    pub id: String,
+}
+
+impl PlexMetadata {
+    fn guid(&self, provider: &str) -> Option<&str> {
+        self.guids.iter().find_map(|guid| {
+            guid.id
+                .strip_prefix(provider)
+                .and_then(|id| id.strip_prefix("://"))
+        })
+    }
+
+    pub fn imdb_id(&self) -> Option<String> {
+        self.guid("imdb").map(|id| id.to_string())
+    }
+
+    pub fn tmdb_id(&self) -> Option<u64> {
+        self.guid("tmdb")?.parse().ok()
+    }
+}
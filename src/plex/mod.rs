@@ -0,0 +1,202 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::{server_client, validate_server_url, Environment, GlobalClient},
+    tmdb::{imdb_id_from_provider_ids, tmdb_api_key},
+};
+use anyhow::{anyhow, Context, Result};
+use api::{PlexMetadata, PlexResponse};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinSet;
+
+pub mod api;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlexCatalog {
+    endpoint: PlexEndpoint,
+    catalog_type: ContentType,
+    token: String,
+    // Plex Media Server holding the collection, must be public unless the server sets
+    // ALLOW_PRIVATE_SERVERS
+    server_url: Option<String>,
+    // Rating key of the collection
+    collection_id: Option<String>,
+    // Overrides the server's TMDB_API_KEY for items that only have a TMDB id
+    tmdb_api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum PlexEndpoint {
+    Watchlist,
+    Collection,
+}
+
+impl PlexCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let plex_catalog: PlexCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = plex_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from Plex catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert Plex CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let offset = (pagination.page - 1) * pagination.page_size;
+        let metadata = self.fetch_metadata(offset, pagination.page_size).await?;
+
+        // Collections can mix movies and shows
+        let plex_type = plex_type(self.catalog_type.media_kind());
+        let metadata = metadata
+            .into_iter()
+            .filter(|item| item.metadata_type.as_deref().is_none_or(|t| t == plex_type))
+            .collect();
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = catalog_metas(
+            metadata,
+            &self.catalog_type,
+            tmdb_api_key(&self.tmdb_api_key).ok(),
+        )
+        .await?;
+        Ok(catalog_response)
+    }
+
+    fn url(&self) -> Result<Url> {
+        let (mut url, path_segments) = match self.endpoint {
+            PlexEndpoint::Watchlist => {
+                let env = Environment::get()
+                    .context("Unable to get global Environment for Plex query")?;
+                let url = Url::parse(&env.plex_discover_url)
+                    .context("Unable to parse PLEX_DISCOVER_URL")?;
+                (url, vec!["library", "sections", "watchlist", "all"])
+            }
+            PlexEndpoint::Collection => {
+                let server_url = self
+                    .server_url
+                    .as_deref()
+                    .context("Plex Collection requires a server_url")?;
+                let collection_id = self
+                    .collection_id
+                    .as_deref()
+                    .context("Plex Collection requires a collection_id")?;
+                let url = validate_server_url(server_url).context("Invalid Plex server URL")?;
+                (
+                    url,
+                    vec!["library", "collections", collection_id, "children"],
+                )
+            }
+        };
+
+        url.path_segments_mut()
+            .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+            .pop_if_empty()
+            .extend(path_segments);
+
+        if let PlexEndpoint::Watchlist = self.endpoint {
+            let type_number = match self.catalog_type.media_kind() {
+                CatalogType::Movie => "1",
+                CatalogType::Series => "2",
+            };
+            url.query_pairs_mut().append_pair("type", type_number);
+        }
+
+        Ok(url)
+    }
+
+    async fn fetch_metadata(&self, offset: i32, limit: i32) -> Result<Vec<PlexMetadata>> {
+        let mut url = self.url()?;
+        url.query_pairs_mut()
+            .append_pair("includeGuids", "1")
+            .append_pair("X-Plex-Container-Start", &offset.to_string())
+            .append_pair("X-Plex-Container-Size", &limit.to_string());
+
+        // Plex's own discover service is trusted, a user's server goes through server_client
+        let client = match self.endpoint {
+            PlexEndpoint::Watchlist => GlobalClient::get()?,
+            PlexEndpoint::Collection => server_client()?,
+        };
+        let response = client
+            .get(url)
+            .header("Accept", "application/json")
+            .header("X-Plex-Token", &self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let response: PlexResponse = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Unable to convert Plex response to json: {}", e.to_string()))?;
+
+        Ok(response.media_container.metadata)
+    }
+}
+
+fn plex_type(catalog_type: CatalogType) -> &'static str {
+    match catalog_type {
+        CatalogType::Movie => "movie",
+        CatalogType::Series => "show",
+    }
+}
+
+async fn catalog_metas(
+    metadata: Vec<PlexMetadata>,
+    catalog_type: &ContentType,
+    api_key: Option<String>,
+) -> Result<Vec<CatalogMeta>> {
+    let media_kind = catalog_type.media_kind();
+    // Spawned all at once, the TMDB fallback bounds how many of its lookups go out together
+    let mut lookups = JoinSet::new();
+
+    for (position, item) in metadata.into_iter().enumerate() {
+        let api_key = api_key.clone();
+        lookups.spawn(async move {
+            let imdb_id = imdb_id_from_provider_ids(
+                media_kind,
+                item.imdb_id(),
+                item.tmdb_id(),
+                api_key.as_deref(),
+            )
+            .await;
+            (position, item, imdb_id)
+        });
+    }
+
+    let mut resolved = Vec::new();
+    while let Some(lookup) = lookups.join_next().await {
+        let (position, item, imdb_id) = lookup?;
+        match imdb_id {
+            Ok(Some(imdb_id)) => resolved.push((position, item, imdb_id)),
+            Ok(None) => {}
+            Err(e) => println!("Unable to resolve IMDb id for {}: {}", item.title, e),
+        }
+    }
+    resolved.sort_by_key(|(position, _, _)| *position);
+
+    Ok(resolved
+        .into_iter()
+        .map(|(_, item, imdb_id)| CatalogMeta {
+            description: item.summary,
+            genres: (!item.genres.is_empty())
+                .then(|| item.genres.into_iter().map(|genre| genre.tag).collect()),
+            release_info: item.year.map(|year| year.to_string()),
+            ..CatalogMeta::from_id(imdb_id, item.title, catalog_type.clone())
+        })
+        .collect())
+}
@@ -0,0 +1,311 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::{read_capped_body, validate_public_url, PublicClient},
+    tmdb::{imdb_id_for, search_imdb_id, tmdb_api_key},
+};
+use anyhow::{anyhow, Context, Result};
+use feed_rs::model::Entry;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+
+// How long a feed is reused when the config doesn't say
+const DEFAULT_REFRESH_MINUTES: u64 = 60;
+// Feeds are refetched at most this often, whatever the config says
+const MIN_REFRESH_MINUTES: u64 = 5;
+// Feeds are user supplied, anything larger than this is turned away
+const MAX_FEED_BYTES: usize = 5 * 1024 * 1024;
+// Newest items resolved to IMDb ids, older ones are dropped
+const MAX_FEED_ITEMS: usize = 500;
+// Items looked up on TMDB at once
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+
+// Each feed keeps the refresh interval it was fetched under, so expired ones can be found
+type FeedCache = HashMap<(String, CatalogType), (Instant, Duration, Arc<Vec<FeedTitle>>)>;
+
+static FEEDS: Lazy<Mutex<FeedCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RssCatalog {
+    catalog_type: ContentType,
+    // RSS or Atom feed
+    url: String,
+    refresh_minutes: Option<u64>,
+    // Overrides the server's TMDB_API_KEY for TMDB ids and title lookups
+    tmdb_api_key: Option<String>,
+}
+
+/// What could be pulled out of a single feed item
+#[derive(Debug, Clone)]
+struct FeedItem {
+    imdb_id: Option<String>,
+    tmdb_id: Option<u64>,
+    title: String,
+    year: Option<u32>,
+    // Unix timestamp (seconds)
+    published_at: Option<i64>,
+}
+
+/// A feed item resolved to an IMDb id
+#[derive(Debug)]
+struct FeedTitle {
+    imdb_id: String,
+    title: String,
+    year: Option<u32>,
+}
+
+impl RssCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let rss_catalog: RssCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = rss_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from RSS feed: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert RSS CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let titles = self.titles().await?;
+
+        let offset = ((pagination.page - 1) * pagination.page_size) as usize;
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = titles
+            .iter()
+            .skip(offset)
+            .take(pagination.page_size as usize)
+            .map(|title| CatalogMeta {
+                release_info: title.year.map(|year| year.to_string()),
+                ..CatalogMeta::from_id(
+                    title.imdb_id.clone(),
+                    title.title.clone(),
+                    self.catalog_type.clone(),
+                )
+            })
+            .collect();
+        Ok(catalog_response)
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        let minutes = self
+            .refresh_minutes
+            .unwrap_or(DEFAULT_REFRESH_MINUTES)
+            .max(MIN_REFRESH_MINUTES);
+        Duration::from_secs(minutes * 60)
+    }
+
+    // The whole feed is resolved at once, feeds are short and get reordered by date
+    async fn titles(&self) -> Result<Arc<Vec<FeedTitle>>> {
+        let cache_key = (self.url.clone(), self.catalog_type.media_kind());
+
+        if let Some((fetched_at, _, titles)) = FEEDS.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < self.refresh_interval() {
+                return Ok(titles.clone());
+            }
+        }
+
+        let mut items = fetch_feed(&self.url).await?;
+        // Newest first, undated items keep their feed order after the dated ones
+        items.sort_by_key(|item| std::cmp::Reverse(item.published_at));
+        items.truncate(MAX_FEED_ITEMS);
+
+        let titles = Arc::new(self.resolve(items).await?);
+
+        let mut feeds = FEEDS.lock().await;
+        // Expired feeds are dropped as new ones come in so the cache can't grow without bound
+        feeds.retain(|_, (fetched_at, refresh_interval, _)| {
+            fetched_at.elapsed() < *refresh_interval
+        });
+        feeds.insert(
+            cache_key,
+            (Instant::now(), self.refresh_interval(), titles.clone()),
+        );
+
+        Ok(titles)
+    }
+
+    async fn resolve(&self, items: Vec<FeedItem>) -> Result<Vec<FeedTitle>> {
+        let api_key = tmdb_api_key(&self.tmdb_api_key).ok();
+        let catalog_type = self.catalog_type.media_kind();
+
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_LOOKUPS));
+        let mut lookups = JoinSet::new();
+        for (position, item) in items.into_iter().enumerate() {
+            let api_key = api_key.clone();
+            let permits = permits.clone();
+            lookups.spawn(async move {
+                let _permit = permits.acquire().await;
+                let imdb_id = resolve_imdb_id(&item, catalog_type, api_key.as_deref()).await;
+                (position, item, imdb_id)
+            });
+        }
+
+        let mut resolved = Vec::new();
+        while let Some(lookup) = lookups.join_next().await {
+            let (position, item, imdb_id) = lookup?;
+            match imdb_id {
+                Ok(Some(imdb_id)) => resolved.push((position, item, imdb_id)),
+                Ok(None) => {}
+                Err(e) => println!("Unable to resolve IMDb id for {}: {}", item.title, e),
+            }
+        }
+        resolved.sort_by_key(|(position, _, _)| *position);
+
+        // Feeds often mention the same title more than once, e.g. rewatches
+        let mut seen = HashSet::new();
+        Ok(resolved
+            .into_iter()
+            .filter(|(_, _, imdb_id)| seen.insert(imdb_id.clone()))
+            .map(|(_, item, imdb_id)| FeedTitle {
+                imdb_id,
+                title: item.title,
+                year: item.year,
+            })
+            .collect())
+    }
+}
+
+async fn resolve_imdb_id(
+    item: &FeedItem,
+    catalog_type: CatalogType,
+    api_key: Option<&str>,
+) -> Result<Option<String>> {
+    if let Some(imdb_id) = &item.imdb_id {
+        return Ok(Some(imdb_id.clone()));
+    }
+
+    let Some(api_key) = api_key else {
+        return Ok(None);
+    };
+
+    match item.tmdb_id {
+        Some(tmdb_id) => imdb_id_for(catalog_type, tmdb_id, api_key).await,
+        None => search_imdb_id(catalog_type, &item.title, item.year, api_key).await,
+    }
+}
+
+async fn fetch_feed(url: &str) -> Result<Vec<FeedItem>> {
+    let url = validate_public_url(url)?;
+    let client = PublicClient::get()?;
+
+    let response = client.get(url).send().await?.error_for_status()?;
+    let body = read_capped_body(response, MAX_FEED_BYTES)
+        .await
+        .context("Unable to read RSS/Atom feed")?;
+
+    let feed = feed_rs::parser::parse(body.as_slice())
+        .map_err(|e| anyhow!("Unable to parse RSS/Atom feed: {}", e))?;
+
+    Ok(feed.entries.iter().filter_map(feed_item).collect())
+}
+
+fn feed_item(entry: &Entry) -> Option<FeedItem> {
+    let entry_title = entry.title.as_ref().map(|title| title.content.trim())?;
+
+    // Ids can hide in links, the description or the full content
+    let mut text: Vec<&str> = entry.links.iter().map(|link| link.href.as_str()).collect();
+    text.push(&entry.id);
+    if let Some(summary) = &entry.summary {
+        text.push(&summary.content);
+    }
+    if let Some(body) = entry
+        .content
+        .as_ref()
+        .and_then(|content| content.body.as_ref())
+    {
+        text.push(body);
+    }
+    let text = text.join(" ");
+
+    let (title, year) = title_and_year(entry_title);
+
+    Some(FeedItem {
+        imdb_id: imdb_id_in(&text),
+        tmdb_id: tmdb_id_in(&text),
+        title,
+        year,
+        published_at: entry
+            .published
+            .or(entry.updated)
+            .map(|date| date.timestamp()),
+    })
+}
+
+// First tt1234567 style id not glued to other letters or digits
+fn imdb_id_in(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    text.match_indices("tt").find_map(|(start, _)| {
+        let preceded_by_alphanumeric = start > 0 && bytes[start - 1].is_ascii_alphanumeric();
+        let digits = text[start + 2..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+
+        (!preceded_by_alphanumeric && digits >= 7)
+            .then(|| text[start..start + 2 + digits].to_string())
+    })
+}
+
+// e.g. https://www.themoviedb.org/movie/603-the-matrix
+fn tmdb_id_in(text: &str) -> Option<u64> {
+    text.split("themoviedb.org/")
+        .skip(1)
+        .find_map(|after_host| {
+            let (_, after_kind) = after_host.split_once('/')?;
+            let digits: String = after_kind
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            digits.parse().ok()
+        })
+}
+
+// Handles "Title (2023)" and Letterboxd's "Title, 2023 - ★★★★"
+fn title_and_year(title: &str) -> (String, Option<u32>) {
+    // Only a rating is dropped, dashes inside titles stay
+    let title = match title.rsplit_once(" - ") {
+        Some((before, rating)) if !rating.chars().any(|c| c.is_alphanumeric()) => before,
+        _ => title,
+    }
+    .trim();
+
+    let parse_year = |year: &str| {
+        let year = year.trim();
+        (year.len() == 4)
+            .then(|| year.parse::<u32>().ok())
+            .flatten()
+    };
+
+    if let Some((name, rest)) = title.rsplit_once(" (") {
+        if let Some(year) = rest.strip_suffix(')').and_then(parse_year) {
+            return (name.trim().to_string(), Some(year));
+        }
+    }
+
+    if let Some((name, rest)) = title.rsplit_once(", ") {
+        if let Some(year) = parse_year(rest) {
+            return (name.trim().to_string(), Some(year));
+        }
+    }
+
+    (title.to_string(), None)
+}
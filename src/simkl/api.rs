@@ -0,0 +1,61 @@
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+// Simkl sends most ids as strings, but not consistently
+fn loose_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let value = Option::<Value>::deserialize(deserializer)?;
+    Ok(match value {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(Value::String(string)) => string.parse().ok(),
+        _ => None,
+    })
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct SimklIds {
+    #[serde(default, alias = "simkl_id", deserialize_with = "loose_id")]
+    pub simkl: Option<u64>,
+    pub slug: Option<String>,
+    pub imdb: Option<String>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub tmdb: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub tvdb: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub mal: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub kitsu: Option<u64>,
+    #[serde(default, deserialize_with = "loose_id")]
+    pub anilist: Option<u64>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct SimklMedia {
+    pub title: String,
+    pub year: Option<u32>,
+    pub ids: SimklIds,
+    pub poster: Option<String>,
+    pub fanart: Option<String>,
+    pub overview: Option<String>,
+    pub genres: Option<Vec<String>>,
+    pub runtime: Option<Value>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct SimklWatchlistItem {
+    pub status: Option<String>,
+    #[serde(alias = "show", alias = "anime")]
+    pub movie: SimklMedia,
+}
+
+// `/sync/all-items` answers with `null` rather than empty lists for an empty watchlist
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Default)]
+pub struct SimklWatchlist {
+    pub movies: Option<Vec<SimklWatchlistItem>>,
+    pub shows: Option<Vec<SimklWatchlistItem>>,
+    pub anime: Option<Vec<SimklWatchlistItem>>,
+}
@@ -0,0 +1,89 @@
+use super::{api_url, simkl_client_id};
+use crate::globals::GlobalClient;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// User token embedded in the catalog config for endpoints that require OAuth. Simkl tokens
+/// don't expire, so unlike Trakt there is nothing to refresh.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimklAuth {
+    pub access_token: String,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimklDeviceCode {
+    pub user_code: String,
+    pub verification_url: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct SimklPinResponse {
+    result: String,
+    access_token: Option<String>,
+    message: Option<String>,
+}
+
+/// State of a PIN authorization, sent back to the configure dashboard while polling
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SimklDeviceStatus {
+    Pending,
+    SlowDown,
+    Authorized { auth: SimklAuth },
+}
+
+/// Starts the PIN flow, the user enters `user_code` at `verification_url`
+pub async fn request_device_code() -> Result<SimklDeviceCode> {
+    let mut url = api_url(&["oauth", "pin"])?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &simkl_client_id()?);
+
+    let client = GlobalClient::get()?;
+    let response = client.get(url).send().await?.error_for_status()?;
+
+    let device_code: SimklDeviceCode = response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert Simkl PIN code response to json: {}",
+            e.to_string()
+        )
+    })?;
+
+    Ok(device_code)
+}
+
+/// Checks whether the user has entered the PIN yet
+pub async fn poll_device_token(user_code: &str) -> Result<SimklDeviceStatus> {
+    let mut url = api_url(&["oauth", "pin", user_code])?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &simkl_client_id()?);
+
+    let client = GlobalClient::get()?;
+    let response = client.get(url).send().await?.error_for_status()?;
+
+    let pin: SimklPinResponse = response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert Simkl PIN token response to json: {}",
+            e.to_string()
+        )
+    })?;
+
+    // Simkl answers KO with a message until the PIN has been entered
+    let status = match (pin.result.as_str(), pin.access_token) {
+        ("OK", Some(access_token)) => SimklDeviceStatus::Authorized {
+            auth: SimklAuth { access_token },
+        },
+        _ if pin
+            .message
+            .is_some_and(|message| message.to_lowercase().contains("slow")) =>
+        {
+            SimklDeviceStatus::SlowDown
+        }
+        _ => SimklDeviceStatus::Pending,
+    };
+
+    Ok(status)
+}
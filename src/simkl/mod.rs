@@ -0,0 +1,265 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, PaginationDetails,
+    },
+    globals::{Environment, GlobalClient},
+    mapping::{ExternalIds, IdMapping},
+};
+use anyhow::{anyhow, Context, Result};
+use api::{SimklMedia, SimklWatchlist};
+use auth::SimklAuth;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+pub mod api;
+pub mod auth;
+
+const SIMKL_IMAGE_URL: &str = "https://simkl.in";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimklCatalog {
+    endpoint: SimklEndpoint,
+    // Simkl keeps anime apart from other shows, so unlike most sources the type is kept as is
+    catalog_type: ContentType,
+    interval: Option<SimklInterval>,
+    best_filter: Option<SimklBestFilter>,
+    status: Option<SimklWatchlistStatus>,
+    auth: Option<SimklAuth>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SimklEndpoint {
+    Trending,
+    Best,
+    Watchlist,
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimklInterval {
+    Today,
+    #[default]
+    Week,
+    Month,
+}
+
+impl SimklInterval {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SimklInterval::Today => "today",
+            SimklInterval::Week => "week",
+            SimklInterval::Month => "month",
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimklBestFilter {
+    #[default]
+    All,
+    Year,
+    Month,
+    Voted,
+    Watched,
+}
+
+impl SimklBestFilter {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SimklBestFilter::All => "all",
+            SimklBestFilter::Year => "year",
+            SimklBestFilter::Month => "month",
+            SimklBestFilter::Voted => "voted",
+            SimklBestFilter::Watched => "watched",
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimklWatchlistStatus {
+    #[default]
+    PlanToWatch,
+    Watching,
+    Completed,
+    Hold,
+    Dropped,
+}
+
+impl SimklWatchlistStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SimklWatchlistStatus::PlanToWatch => "plantowatch",
+            SimklWatchlistStatus::Watching => "watching",
+            SimklWatchlistStatus::Completed => "completed",
+            SimklWatchlistStatus::Hold => "hold",
+            SimklWatchlistStatus::Dropped => "dropped",
+        }
+    }
+}
+
+impl SimklCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let simkl_catalog: SimklCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = simkl_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from Simkl catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert Simkl CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let media = self.fetch_media().await?;
+
+        // Simkl hands back whole lists, pages are cut out locally
+        let offset = ((pagination.page - 1) * pagination.page_size) as usize;
+        let mapping = IdMapping::get()?;
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = media
+            .into_iter()
+            .skip(offset)
+            .take(pagination.page_size as usize)
+            .filter_map(|media| catalog_meta(media, &self.catalog_type, mapping))
+            .collect();
+        Ok(catalog_response)
+    }
+
+    async fn fetch_media(&self) -> Result<Vec<SimklMedia>> {
+        let media_kind = match self.catalog_type {
+            ContentType::Movie => "movies",
+            ContentType::Anime => "anime",
+            _ => "tv",
+        };
+
+        match self.endpoint {
+            SimklEndpoint::Trending => {
+                let interval = self.interval.unwrap_or_default();
+                let mut url = api_url(&[media_kind, "trending", interval.as_str()])?;
+                url.query_pairs_mut()
+                    .append_pair("extended", "overview,genres");
+                get_json(url, &None).await
+            }
+            SimklEndpoint::Best => {
+                let filter = self.best_filter.unwrap_or_default();
+                let url = api_url(&[media_kind, "best", filter.as_str()])?;
+                get_json(url, &None).await
+            }
+            SimklEndpoint::Watchlist => {
+                if self.auth.is_none() {
+                    return Err(anyhow!("Simkl Watchlist requires auth"));
+                }
+
+                let sync_kind = match self.catalog_type {
+                    ContentType::Movie => "movies",
+                    ContentType::Anime => "anime",
+                    _ => "shows",
+                };
+                let status = self.status.unwrap_or_default();
+                let url = api_url(&["sync", "all-items", sync_kind, status.as_str()])?;
+
+                let watchlist: Option<SimklWatchlist> = get_json(url, &self.auth).await?;
+                let watchlist = watchlist.unwrap_or_default();
+                let items = match self.catalog_type {
+                    ContentType::Movie => watchlist.movies,
+                    ContentType::Anime => watchlist.anime,
+                    _ => watchlist.shows,
+                };
+
+                Ok(items
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|item| item.movie)
+                    .collect())
+            }
+        }
+    }
+}
+
+fn catalog_meta(
+    media: SimklMedia,
+    catalog_type: &ContentType,
+    mapping: &IdMapping,
+) -> Option<CatalogMeta> {
+    let media_kind = catalog_type.media_kind();
+    // TMDB ids are only trusted for movies as TMDB shares them between types
+    let external_ids = ExternalIds {
+        anilist: media.ids.anilist,
+        mal: media.ids.mal,
+        kitsu: media.ids.kitsu,
+        tmdb: media.ids.tmdb.filter(|_| media_kind == CatalogType::Movie),
+        tvdb: media.ids.tvdb.filter(|_| media_kind == CatalogType::Series),
+    };
+    let id = mapping.stremio_id(media.ids.imdb.as_deref(), &external_ids)?;
+
+    let meta = CatalogMeta::from_id(id, media.title, catalog_type.clone());
+
+    // Metahub only covers IMDb ids, Simkl's own artwork fills in for the rest
+    Some(CatalogMeta {
+        poster: meta.poster.clone().or(media
+            .poster
+            .map(|poster| format!("{}/posters/{}_m.jpg", SIMKL_IMAGE_URL, poster))),
+        background: meta.background.clone().or(media
+            .fanart
+            .map(|fanart| format!("{}/fanart/{}_medium.jpg", SIMKL_IMAGE_URL, fanart))),
+        description: media.overview,
+        genres: media.genres,
+        release_info: media.year.map(|year| year.to_string()),
+        ..meta
+    })
+}
+
+fn simkl_client_id() -> Result<String> {
+    let env = Environment::get().context("Unable to get global Environment for Simkl query")?;
+    env.simkl_client_id
+        .clone()
+        .context("Simkl requires SIMKL_CLIENT_ID on the server")
+}
+
+fn api_url(path_segments: &[&str]) -> Result<Url> {
+    let env = Environment::get().context("Unable to get global Environment for Simkl query")?;
+
+    let mut url = Url::parse(&env.simkl_api_url)?;
+    url.path_segments_mut()
+        .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+        .pop_if_empty()
+        .extend(path_segments);
+
+    Ok(url)
+}
+
+async fn get_json<T: DeserializeOwned>(url: Url, auth: &Option<SimklAuth>) -> Result<T> {
+    let client = GlobalClient::get()?;
+
+    let mut request = client
+        .get(url)
+        .header("Content-Type", "application/json")
+        .header("simkl-api-key", simkl_client_id()?);
+
+    if let Some(auth) = auth {
+        request = request.bearer_auth(&auth.access_token);
+    }
+
+    let response = request.send().await?.error_for_status()?;
+
+    response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert Simkl API response to json: {}",
+            e.to_string()
+        )
+    })
+}
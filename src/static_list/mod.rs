@@ -0,0 +1,187 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, ContentType,
+        PaginationDetails,
+    },
+    uploads::UploadStore,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+const UPLOAD_KIND: &str = "static";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StaticCatalog {
+    catalog_type: ContentType,
+    // Id handed back by /static/upload
+    list_id: String,
+}
+
+/// One entry of a user supplied list, everything but the IMDb id is optional
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaticItem {
+    pub id: String,
+    pub name: Option<String>,
+    pub poster: Option<String>,
+    pub background: Option<String>,
+    // Lets a single list feed catalogs of several types, an item only shows up in catalogs of
+    // exactly its type
+    #[serde(rename = "type")]
+    pub catalog_type: Option<ContentType>,
+}
+
+// Uploads may be a bare list of ids or a list of items
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StaticUploadEntry {
+    Id(String),
+    Item(StaticItem),
+}
+
+impl StaticCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let static_catalog: StaticCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let catalog_response = static_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from static list: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert static CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let items: Arc<Vec<StaticItem>> = UploadStore::load(UPLOAD_KIND, &self.list_id).await?;
+
+        let offset = ((pagination.page - 1) * pagination.page_size) as usize;
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = items
+            .iter()
+            .filter(|item| {
+                item.catalog_type
+                    .as_ref()
+                    .is_none_or(|t| t == &self.catalog_type)
+            })
+            .skip(offset)
+            .take(pagination.page_size as usize)
+            .map(|item| catalog_meta(item.clone(), self.catalog_type.clone()))
+            .collect();
+        Ok(catalog_response)
+    }
+}
+
+fn catalog_meta(item: StaticItem, catalog_type: ContentType) -> CatalogMeta {
+    let name = item.name.unwrap_or_else(|| item.id.clone());
+    let meta = CatalogMeta::from_id(item.id, name, catalog_type);
+
+    // Custom artwork wins over metahub's
+    CatalogMeta {
+        poster: item.poster.or(meta.poster.clone()),
+        background: item.background.or(meta.background.clone()),
+        ..meta
+    }
+}
+
+/// Parses an uploaded list (JSON or CSV) and stores it, returning the id for a catalog config
+pub async fn upload_list(body: &str, is_csv: bool) -> Result<String> {
+    let items = if is_csv {
+        parse_csv(body)?
+    } else {
+        parse_json(body)?
+    };
+
+    if items.is_empty() {
+        return Err(anyhow!("No items found in uploaded list"));
+    }
+
+    if let Some(item) = items.iter().find(|item| !is_imdb_id(&item.id)) {
+        return Err(anyhow!("{} is not an IMDb id", item.id));
+    }
+
+    UploadStore::save(UPLOAD_KIND, &items).await
+}
+
+fn is_imdb_id(id: &str) -> bool {
+    id.len() > 2 && id.starts_with("tt") && id[2..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_json(body: &str) -> Result<Vec<StaticItem>> {
+    let entries: Vec<StaticUploadEntry> =
+        serde_json::from_str(body).context("Uploaded list is not a JSON array of ids or items")?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| match entry {
+            StaticUploadEntry::Id(id) => StaticItem {
+                id,
+                name: None,
+                poster: None,
+                background: None,
+                catalog_type: None,
+            },
+            StaticUploadEntry::Item(item) => item,
+        })
+        .collect())
+}
+
+// Expects a header row, only the id column is required: id,name,poster,background,type
+fn parse_csv(body: &str) -> Result<Vec<StaticItem>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes());
+
+    let headers = reader
+        .headers()
+        .context("Unable to read uploaded CSV headers")?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+    };
+
+    let id_column = column("id").context("Uploaded CSV has no id column")?;
+    let name_column = column("name");
+    let poster_column = column("poster");
+    let background_column = column("background");
+    let type_column = column("type");
+
+    let mut items = Vec::new();
+    for record in reader.records() {
+        let record = record.context("Unable to read uploaded CSV")?;
+        let cell = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+        };
+
+        let Some(id) = cell(Some(id_column)) else {
+            continue;
+        };
+
+        items.push(StaticItem {
+            id,
+            name: cell(name_column),
+            poster: cell(poster_column),
+            background: cell(background_column),
+            // Any Stremio type, same as the type of a JSON item
+            catalog_type: cell(type_column).map(ContentType::from),
+        });
+    }
+
+    Ok(items)
+}
@@ -0,0 +1,27 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct StremioManifest {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub catalogs: Vec<StremioManifestCatalog>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct StremioManifestCatalog {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub catalog_type: String,
+    pub name: Option<String>,
+}
+
+// Metas are kept as raw json so fields we don't model (imdbRating, links, ...) survive the trip
+#[derive(Deserialize, Debug)]
+pub struct StremioCatalogResponse {
+    #[serde(default)]
+    pub metas: Vec<Value>,
+}
@@ -0,0 +1,333 @@
+use crate::{
+    addon::catalog::{
+        decode_config, encode_path_value, CatalogRequestParams, ContentType, PaginationDetails,
+    },
+    globals::{read_capped_body, validate_public_url, PublicClient},
+};
+use anyhow::{anyhow, Context, Result};
+use api::{StremioCatalogResponse, StremioManifest};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+pub mod api;
+
+// Upper bound on upstream pages walked per request when filtering or merging
+const MAX_UPSTREAM_PAGES: usize = 10;
+// How long an upstream manifest is reused, it's only read for the type of a catalog
+const MANIFEST_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+// Upstreams are user supplied, manifests and catalog pages larger than this are turned away
+const MAX_RESPONSE_BYTES: usize = 5 * 1024 * 1024;
+
+type ManifestCache = HashMap<String, (Instant, Arc<StremioManifest>)>;
+
+// Keyed by the upstream manifest URL
+static MANIFESTS: Lazy<Mutex<ManifestCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StremioCatalog {
+    catalog_type: ContentType,
+    upstreams: Vec<StremioUpstream>,
+    include_genres: Option<Vec<String>>,
+    exclude_genres: Option<Vec<String>>,
+    min_year: Option<u32>,
+}
+
+/// A catalog served by another Stremio addon
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StremioUpstream {
+    manifest_url: String,
+    catalog_id: String,
+    // The upstream's own type for this catalog, looked up from its manifest when missing
+    catalog_type: Option<String>,
+    // Fixed extras sent with every request, e.g. a genre
+    extra: Option<BTreeMap<String, String>>,
+}
+
+impl StremioCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let stremio_catalog: StremioCatalog = decode_config(&catalog_request_params.catalog_id)?;
+
+        let metas = stremio_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build catalog from upstream Stremio addons: {}",
+                    e.to_string()
+                )
+            })?;
+
+        Ok(json!({ "metas": metas }))
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<Vec<Value>> {
+        let skip = pagination.skip.max(0) as usize;
+
+        // A single unfiltered upstream lines up with Stremio's skip, pass it straight through
+        if let [upstream] = self.upstreams.as_slice() {
+            if !self.has_filters() {
+                let upstream_type = upstream.upstream_type().await?;
+                return upstream.fetch(&upstream_type, skip).await;
+            }
+        }
+
+        // Otherwise every upstream is walked from the start so skip applies to the final stream
+        let wanted = skip + pagination.page_size as usize;
+        let mut seen_ids = HashSet::new();
+        let mut metas = Vec::new();
+
+        for upstream in &self.upstreams {
+            let upstream_metas = upstream.fetch_until(wanted, |meta| self.keep(meta)).await?;
+
+            for meta in upstream_metas {
+                if let Some(id) = meta.get("id").and_then(Value::as_str) {
+                    if seen_ids.insert(id.to_string()) {
+                        metas.push(meta);
+                    }
+                }
+            }
+        }
+
+        Ok(metas
+            .into_iter()
+            .skip(skip)
+            .take(pagination.page_size as usize)
+            .collect())
+    }
+
+    fn has_filters(&self) -> bool {
+        self.include_genres.is_some() || self.exclude_genres.is_some() || self.min_year.is_some()
+    }
+
+    fn keep(&self, meta: &Value) -> bool {
+        let genres: Vec<String> = meta
+            .get("genres")
+            .and_then(Value::as_array)
+            .map(|genres| {
+                genres
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(|genre| genre.to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let has_genre = |wanted: &Vec<String>| {
+            wanted
+                .iter()
+                .any(|genre| genres.contains(&genre.to_lowercase()))
+        };
+
+        if let Some(include_genres) = &self.include_genres {
+            if !has_genre(include_genres) {
+                return false;
+            }
+        }
+
+        if let Some(exclude_genres) = &self.exclude_genres {
+            if has_genre(exclude_genres) {
+                return false;
+            }
+        }
+
+        if let Some(min_year) = self.min_year {
+            // releaseInfo is either a year or a range like "2016-2020"
+            let year = meta
+                .get("releaseInfo")
+                .and_then(Value::as_str)
+                .and_then(|release_info| release_info.get(..4))
+                .and_then(|year| year.parse::<u32>().ok());
+
+            if year.is_none_or(|year| year < min_year) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl StremioUpstream {
+    fn base_url(&self) -> Result<Url> {
+        // stremio:// links are what addon install buttons hand out
+        let manifest_url = match self.manifest_url.strip_prefix("stremio://") {
+            Some(rest) => format!("https://{}", rest),
+            None => self.manifest_url.clone(),
+        };
+
+        let base_url = manifest_url
+            .strip_suffix("/manifest.json")
+            .unwrap_or(manifest_url.trim_end_matches('/'));
+
+        validate_public_url(base_url)
+    }
+
+    async fn upstream_type(&self) -> Result<String> {
+        if let Some(catalog_type) = &self.catalog_type {
+            return Ok(catalog_type.clone());
+        }
+
+        let manifest = self.manifest().await?;
+        manifest
+            .catalogs
+            .iter()
+            .find(|catalog| catalog.id == self.catalog_id)
+            .map(|catalog| catalog.catalog_type.clone())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Catalog {} not found in upstream manifest {}",
+                    self.catalog_id,
+                    manifest.id
+                )
+            })
+    }
+
+    async fn manifest(&self) -> Result<Arc<StremioManifest>> {
+        let cache_key = self.manifest_url.clone();
+
+        if let Some((fetched_at, manifest)) = MANIFESTS.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < MANIFEST_CACHE_TTL {
+                return Ok(manifest.clone());
+            }
+        }
+
+        let mut url = self.base_url()?;
+        url.path_segments_mut()
+            .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+            .pop_if_empty()
+            .push("manifest.json");
+
+        let manifest: StremioManifest = get_json(url)
+            .await
+            .context("Unable to read upstream manifest")?;
+        let manifest = Arc::new(manifest);
+
+        let mut manifests = MANIFESTS.lock().await;
+        // Expired manifests are dropped as new ones come in so the cache can't grow without bound
+        manifests.retain(|_, (fetched_at, _)| fetched_at.elapsed() < MANIFEST_CACHE_TTL);
+        manifests.insert(cache_key, (Instant::now(), manifest.clone()));
+
+        Ok(manifest)
+    }
+
+    async fn fetch(&self, upstream_type: &str, skip: usize) -> Result<Vec<Value>> {
+        let url = self.catalog_url(upstream_type, skip)?;
+        let response: StremioCatalogResponse = get_json(url)
+            .await
+            .context("Unable to read upstream catalog")?;
+
+        Ok(response.metas)
+    }
+
+    fn catalog_url(&self, upstream_type: &str, skip: usize) -> Result<Url> {
+        // Encoded the way Stremio encodes extras, so values with `&`, `=` or `/` stay whole
+        let mut extra: Vec<String> = self
+            .extra
+            .iter()
+            .flatten()
+            .map(|(name, value)| {
+                format!("{}={}", encode_path_value(name), encode_path_value(value))
+            })
+            .collect();
+        if skip > 0 {
+            extra.push(format!("skip={}", skip));
+        }
+
+        let mut url = self.base_url()?;
+        url.path_segments_mut()
+            .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+            .pop_if_empty()
+            .push("catalog")
+            .push(upstream_type);
+
+        // Set as a whole path, pushing segments would encode the `%` of encoded values again
+        let catalog_id = encode_path_value(&self.catalog_id);
+        let catalog_path = if extra.is_empty() {
+            format!("{}/{}.json", url.path(), catalog_id)
+        } else {
+            format!("{}/{}/{}.json", url.path(), catalog_id, extra.join("&"))
+        };
+        url.set_path(&catalog_path);
+
+        Ok(url)
+    }
+
+    // Pages through the upstream until `wanted` metas pass the filter or it runs out
+    async fn fetch_until(
+        &self,
+        wanted: usize,
+        keep: impl Fn(&Value) -> bool,
+    ) -> Result<Vec<Value>> {
+        let upstream_type = self.upstream_type().await?;
+        let mut upstream_skip = 0;
+        let mut metas = Vec::new();
+
+        for _ in 0..MAX_UPSTREAM_PAGES {
+            let page = self.fetch(&upstream_type, upstream_skip).await?;
+            if page.is_empty() {
+                break;
+            }
+
+            upstream_skip += page.len();
+            metas.extend(page.into_iter().filter(|meta| keep(meta)));
+
+            if metas.len() >= wanted {
+                break;
+            }
+        }
+
+        Ok(metas)
+    }
+}
+
+async fn get_json<T: DeserializeOwned>(url: Url) -> Result<T> {
+    let response = PublicClient::get()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?;
+    let body = read_capped_body(response, MAX_RESPONSE_BYTES).await?;
+
+    serde_json::from_slice(&body).context("Unable to convert upstream response to json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstream(extra: Option<BTreeMap<String, String>>) -> StremioUpstream {
+        StremioUpstream {
+            manifest_url: "stremio://addon.example.com/manifest.json".to_string(),
+            catalog_id: "top".to_string(),
+            catalog_type: None,
+            extra,
+        }
+    }
+
+    #[test]
+    fn plain_catalog_url() {
+        let url = upstream(None).catalog_url("movie", 0).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://addon.example.com/catalog/movie/top.json"
+        );
+    }
+
+    #[test]
+    fn extras_are_encoded_once() {
+        let extra = BTreeMap::from([("genre".to_string(), "Action & Adventure".to_string())]);
+        let url = upstream(Some(extra)).catalog_url("series", 100).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://addon.example.com/catalog/series/top/genre=Action%20%26%20Adventure&skip=100.json"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TmdbPage {
+    pub page: u32,
+    pub results: Vec<TmdbItem>,
+    pub total_pages: u32,
+    pub total_results: u32,
+}
+
+// TMDB v3 lists carry their entries in `items` rather than `results`
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TmdbListPage {
+    pub id: u64,
+    pub name: String,
+    pub page: u32,
+    pub items: Vec<TmdbItem>,
+    pub total_pages: u32,
+}
+
+// Movies use title/release_date, shows use name/first_air_date
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TmdbItem {
+    pub id: u64,
+    pub title: Option<String>,
+    pub name: Option<String>,
+    pub overview: Option<String>,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
+    pub release_date: Option<String>,   // Date as String
+    pub first_air_date: Option<String>, // Date as String
+    pub genre_ids: Option<Vec<u32>>,
+    pub media_type: Option<String>,
+    pub popularity: Option<f64>,
+    pub vote_average: Option<f64>,
+    pub vote_count: Option<u32>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TmdbExternalIds {
+    pub id: u64,
+    pub imdb_id: Option<String>,
+    pub tvdb_id: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TmdbGenreList {
+    pub genres: Vec<TmdbGenre>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TmdbGenre {
+    pub id: u32,
+    pub name: String,
+}
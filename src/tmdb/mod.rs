@@ -0,0 +1,532 @@
+use crate::{
+    addon::catalog::{
+        decode_config, CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType,
+        ContentType, DefaultVideoID, PaginationDetails,
+    },
+    genres::slug,
+    globals::{Environment, GlobalClient},
+};
+use anyhow::{anyhow, Context, Result};
+use api::{TmdbExternalIds, TmdbGenre, TmdbGenreList, TmdbItem, TmdbListPage, TmdbPage};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+
+pub mod api;
+
+const TMDB_IMAGE_URL: &str = "https://image.tmdb.org/t/p";
+// TMDB always pages by 20, several of its pages make up one of ours
+const TMDB_PAGE_SIZE: i32 = 20;
+// TMDB refuses pages past this
+const TMDB_MAX_PAGE: i32 = 500;
+// External id lookups in flight at once across every catalog, TMDB rate limits per IP
+const MAX_CONCURRENT_LOOKUPS: usize = 8;
+// TMDB ids rarely change their IMDb mapping, but lookups are let go eventually so the cache
+// stays bounded
+const IMDB_ID_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+type ImdbIdCache = HashMap<(CatalogType, u64), (Instant, Option<String>)>;
+
+static IMDB_IDS: Lazy<Mutex<ImdbIdCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Shared by every source resolving ids through imdb_id_for, cached ids don't take a permit
+static LOOKUP_PERMITS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_LOOKUPS);
+
+// Genre lists are fixed on TMDB's side, kept for the life of the process as well
+static GENRES: Lazy<Mutex<HashMap<CatalogType, Arc<Vec<TmdbGenre>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TmdbCatalog {
+    endpoint: TmdbEndpoint,
+    catalog_type: ContentType,
+    time_window: Option<TmdbTimeWindow>,
+    list_id: Option<String>,
+    // Discover filters, passed through to TMDB as-is
+    genres: Option<String>,
+    year: Option<u32>,
+    watch_providers: Option<String>,
+    watch_region: Option<String>,
+    sort_by: Option<String>,
+    region: Option<String>,
+    language: Option<String>,
+    // Overrides the server's TMDB_API_KEY
+    api_key: Option<String>,
+    // Query from Stremio's search extra, never part of the stored config
+    #[serde(skip)]
+    search: Option<String>,
+    // Genre from Stremio's genre extra, only Discover filters on it
+    #[serde(skip)]
+    genre: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum TmdbEndpoint {
+    Discover,
+    Trending,
+    Popular,
+    NowPlaying,
+    Upcoming,
+    List,
+    Search,
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TmdbTimeWindow {
+    Day,
+    #[default]
+    Week,
+}
+
+impl TmdbTimeWindow {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TmdbTimeWindow::Day => "day",
+            TmdbTimeWindow::Week => "week",
+        }
+    }
+}
+
+impl TmdbCatalog {
+    pub async fn from_catalog_params(
+        catalog_request_params: &CatalogRequestParams,
+    ) -> Result<Value> {
+        let mut tmdb_catalog: TmdbCatalog = decode_config(&catalog_request_params.catalog_id)?;
+        tmdb_catalog.search = catalog_request_params.search.clone();
+        tmdb_catalog.genre = catalog_request_params.genre.clone();
+
+        let catalog_response = tmdb_catalog
+            .build(&catalog_request_params.pagination)
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Unable to build CatalogResponse from TMDB catalog query: {}",
+                    e.to_string()
+                )
+            })?;
+
+        let output_value = serde_json::to_value(catalog_response)
+            .context("Unable to convert TMDB CatalogResponse to JSON value")?;
+        Ok(output_value)
+    }
+
+    pub async fn build(&self, pagination: &PaginationDetails) -> Result<CatalogResponse> {
+        let api_key = tmdb_api_key(&self.api_key)?;
+        // Metas are tagged with genre names, a catalog without them is still served
+        let genres = genres(self.catalog_type.media_kind(), &api_key)
+            .await
+            .map_err(|e| println!("Unable to fetch TMDB genres: {}", e))
+            .ok();
+
+        let genre_id = match (&self.endpoint, &self.genre) {
+            (TmdbEndpoint::Discover, Some(genre)) => {
+                let genre_slug = slug(genre);
+                let genre_id = genres
+                    .iter()
+                    .flat_map(|genres| genres.iter())
+                    .find(|known| slug(&known.name) == genre_slug)
+                    .map(|known| known.id)
+                    .with_context(|| format!("Unknown TMDB genre {}", genre))?;
+                Some(genre_id)
+            }
+            _ => None,
+        };
+
+        // Walk the TMDB pages that make up the requested page
+        let tmdb_pages_per_page = (pagination.page_size / TMDB_PAGE_SIZE).max(1);
+        let first_tmdb_page = (pagination.page - 1) * tmdb_pages_per_page + 1;
+
+        let mut items = Vec::new();
+        for tmdb_page in first_tmdb_page..first_tmdb_page + tmdb_pages_per_page {
+            if tmdb_page > TMDB_MAX_PAGE {
+                break;
+            }
+
+            let (page_items, total_pages) = self.fetch_page(&api_key, tmdb_page, genre_id).await?;
+            items.extend(page_items);
+
+            if tmdb_page >= total_pages {
+                break;
+            }
+        }
+
+        let genres = genres.as_deref().map(Vec::as_slice).unwrap_or_default();
+        let metas = catalog_metas(items, &self.catalog_type, &api_key, genres).await?;
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = metas;
+        Ok(catalog_response)
+    }
+
+    async fn fetch_page(
+        &self,
+        api_key: &str,
+        page: i32,
+        genre_id: Option<u32>,
+    ) -> Result<(Vec<TmdbItem>, i32)> {
+        let media_kind = media_kind(self.catalog_type.media_kind());
+
+        let endpoint_path_segments = match self.endpoint {
+            TmdbEndpoint::Discover => Ok(vec!["discover", media_kind]),
+            TmdbEndpoint::Trending => Ok(vec![
+                "trending",
+                media_kind,
+                self.time_window.unwrap_or_default().as_str(),
+            ]),
+            TmdbEndpoint::Popular => Ok(vec![media_kind, "popular"]),
+            TmdbEndpoint::NowPlaying => match self.catalog_type.media_kind() {
+                CatalogType::Movie => Ok(vec!["movie", "now_playing"]),
+                CatalogType::Series => Ok(vec!["tv", "on_the_air"]),
+            },
+            TmdbEndpoint::Upcoming => match self.catalog_type.media_kind() {
+                CatalogType::Movie => Ok(vec!["movie", "upcoming"]),
+                CatalogType::Series => Err(anyhow!("TMDB Upcoming is only available for movies")),
+            },
+            TmdbEndpoint::List => match &self.list_id {
+                Some(list_id) => Ok(vec!["list", list_id.as_str()]),
+                None => Err(anyhow!("No list provided in TMDB List endpoint")),
+            },
+            TmdbEndpoint::Search => Ok(vec!["search", media_kind]),
+        }?;
+
+        let mut url = api_url(api_key, &endpoint_path_segments)?;
+        url.query_pairs_mut().append_pair("page", &page.to_string());
+
+        if let Some(language) = &self.language {
+            url.query_pairs_mut().append_pair("language", language);
+        }
+
+        if let Some(region) = &self.region {
+            url.query_pairs_mut().append_pair("region", region);
+        }
+
+        if let TmdbEndpoint::Discover = self.endpoint {
+            self.append_discover_filters(&mut url, genre_id);
+        }
+
+        if let TmdbEndpoint::Search = self.endpoint {
+            let query = self
+                .search
+                .as_deref()
+                .context("TMDB Search requires a search query")?;
+            url.query_pairs_mut().append_pair("query", query);
+        }
+
+        match self.endpoint {
+            TmdbEndpoint::List => {
+                let list_page: TmdbListPage = get_json(url).await?;
+                // Lists can mix movies and shows, only keep the ones matching this catalog
+                let items = list_page
+                    .items
+                    .into_iter()
+                    .filter(|item| item.media_type.as_deref() == Some(media_kind))
+                    .collect();
+                Ok((items, list_page.total_pages as i32))
+            }
+            _ => {
+                let tmdb_page: TmdbPage = get_json(url).await?;
+                Ok((tmdb_page.results, tmdb_page.total_pages as i32))
+            }
+        }
+    }
+
+    fn append_discover_filters(&self, url: &mut Url, genre_id: Option<u32>) {
+        let mut query_pairs = url.query_pairs_mut();
+
+        // A comma has TMDB match every genre, so the requested one narrows the configured ones
+        let with_genres = match (&self.genres, genre_id) {
+            (Some(genres), Some(genre_id)) => Some(format!("{},{}", genres, genre_id)),
+            (Some(genres), None) => Some(genres.clone()),
+            (None, Some(genre_id)) => Some(genre_id.to_string()),
+            (None, None) => None,
+        };
+        if let Some(with_genres) = with_genres {
+            query_pairs.append_pair("with_genres", &with_genres);
+        }
+
+        if let Some(year) = self.year {
+            let year_filter = match self.catalog_type.media_kind() {
+                CatalogType::Movie => "primary_release_year",
+                CatalogType::Series => "first_air_date_year",
+            };
+            query_pairs.append_pair(year_filter, &year.to_string());
+        }
+
+        if let Some(watch_providers) = &self.watch_providers {
+            query_pairs.append_pair("with_watch_providers", watch_providers);
+        }
+
+        if let Some(watch_region) = &self.watch_region {
+            query_pairs.append_pair("watch_region", watch_region);
+        }
+
+        if let Some(sort_by) = &self.sort_by {
+            query_pairs.append_pair("sort_by", sort_by);
+        }
+    }
+}
+
+fn media_kind(catalog_type: CatalogType) -> &'static str {
+    match catalog_type {
+        CatalogType::Movie => "movie",
+        CatalogType::Series => "tv",
+    }
+}
+
+/// TMDB's genres for a catalog type
+pub async fn genres(catalog_type: CatalogType, api_key: &str) -> Result<Arc<Vec<TmdbGenre>>> {
+    if let Some(genres) = GENRES.lock().await.get(&catalog_type) {
+        return Ok(genres.clone());
+    }
+
+    let url = api_url(api_key, &["genre", media_kind(catalog_type), "list"])?;
+    let genre_list: TmdbGenreList = get_json(url).await?;
+    let genres = Arc::new(genre_list.genres);
+
+    GENRES.lock().await.insert(catalog_type, genres.clone());
+    Ok(genres)
+}
+
+/// The API key from a catalog config, falling back to the server's TMDB_API_KEY
+pub fn tmdb_api_key(config_api_key: &Option<String>) -> Result<String> {
+    if let Some(api_key) = config_api_key {
+        return Ok(api_key.clone());
+    }
+
+    let env = Environment::get().context("Unable to get global Environment for TMDB query")?;
+    env.tmdb_api_key
+        .clone()
+        .context("No TMDB API key in the catalog config or the server environment")
+}
+
+fn api_url(api_key: &str, path_segments: &[&str]) -> Result<Url> {
+    let env = Environment::get().context("Unable to get global Environment for TMDB query")?;
+
+    let mut url = Url::parse(&env.tmdb_api_url)?;
+    url.path_segments_mut()
+        .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+        .pop_if_empty()
+        .extend(path_segments);
+    url.query_pairs_mut().append_pair("api_key", api_key);
+
+    Ok(url)
+}
+
+// reqwest errors name the URL they failed on, which carries the api_key, so it is dropped
+async fn get_json<T: DeserializeOwned>(url: Url) -> Result<T> {
+    let client = GlobalClient::get()?;
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)?
+        .error_for_status()
+        .map_err(reqwest::Error::without_url)?;
+
+    response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert TMDB API response to json: {}",
+            e.without_url().to_string()
+        )
+    })
+}
+
+/// Looks up the IMDb id for a TMDB movie or show through its external ids. At most
+/// `MAX_CONCURRENT_LOOKUPS` of these go out at once, however many callers spawn.
+pub async fn imdb_id_for(
+    catalog_type: CatalogType,
+    tmdb_id: u64,
+    api_key: &str,
+) -> Result<Option<String>> {
+    if let Some((fetched_at, imdb_id)) = IMDB_IDS.lock().await.get(&(catalog_type, tmdb_id)) {
+        if fetched_at.elapsed() < IMDB_ID_CACHE_TTL {
+            return Ok(imdb_id.clone());
+        }
+    }
+
+    let tmdb_id_string = tmdb_id.to_string();
+    let url = api_url(
+        api_key,
+        &[media_kind(catalog_type), &tmdb_id_string, "external_ids"],
+    )?;
+    let external_ids: TmdbExternalIds = {
+        let _permit = LOOKUP_PERMITS.acquire().await?;
+        get_json(url).await?
+    };
+    let imdb_id = external_ids.imdb_id.filter(|id| !id.is_empty());
+
+    let mut imdb_ids = IMDB_IDS.lock().await;
+    // Expired ids are dropped as new ones come in so the cache can't grow without bound
+    imdb_ids.retain(|_, (fetched_at, _)| fetched_at.elapsed() < IMDB_ID_CACHE_TTL);
+    imdb_ids.insert((catalog_type, tmdb_id), (Instant::now(), imdb_id.clone()));
+
+    Ok(imdb_id)
+}
+
+/// IMDb id from whichever provider ids a source knows, going through TMDB only when the IMDb id
+/// is missing and a TMDB API key is available
+pub async fn imdb_id_from_provider_ids(
+    catalog_type: CatalogType,
+    imdb_id: Option<String>,
+    tmdb_id: Option<u64>,
+    api_key: Option<&str>,
+) -> Result<Option<String>> {
+    if let Some(imdb_id) = imdb_id.filter(|id| id.starts_with("tt")) {
+        return Ok(Some(imdb_id));
+    }
+
+    match (tmdb_id, api_key) {
+        (Some(tmdb_id), Some(api_key)) => imdb_id_for(catalog_type, tmdb_id, api_key).await,
+        _ => Ok(None),
+    }
+}
+
+/// Best effort IMDb id for a title, for sources that only know titles and years
+pub async fn search_imdb_id(
+    catalog_type: CatalogType,
+    title: &str,
+    year: Option<u32>,
+    api_key: &str,
+) -> Result<Option<String>> {
+    let mut url = api_url(api_key, &["search", media_kind(catalog_type)])?;
+    url.query_pairs_mut().append_pair("query", title);
+
+    if let Some(year) = year {
+        let year_filter = match catalog_type {
+            CatalogType::Movie => "year",
+            CatalogType::Series => "first_air_date_year",
+        };
+        url.query_pairs_mut()
+            .append_pair(year_filter, &year.to_string());
+    }
+
+    let search_page: TmdbPage = get_json(url).await?;
+
+    match search_page.results.first() {
+        Some(item) => imdb_id_for(catalog_type, item.id, api_key).await,
+        None => Ok(None),
+    }
+}
+
+// IMDb ids are resolved a few at a time (see imdb_id_for), items without one are dropped like
+// on the Trakt side
+async fn catalog_metas(
+    items: Vec<TmdbItem>,
+    catalog_type: &ContentType,
+    api_key: &str,
+    genres: &[TmdbGenre],
+) -> Result<Vec<CatalogMeta>> {
+    let media_kind = catalog_type.media_kind();
+    let mut lookups = JoinSet::new();
+
+    for (position, item) in items.into_iter().enumerate() {
+        let api_key = api_key.to_string();
+        lookups.spawn(async move {
+            let imdb_id = imdb_id_for(media_kind, item.id, &api_key).await;
+            (position, item, imdb_id)
+        });
+    }
+
+    let mut resolved = Vec::new();
+    while let Some(lookup) = lookups.join_next().await {
+        let (position, item, imdb_id) = lookup?;
+        match imdb_id {
+            Ok(Some(imdb_id)) => resolved.push((
+                position,
+                catalog_meta(item, imdb_id, catalog_type.clone(), genres),
+            )),
+            Ok(None) => {}
+            Err(e) => println!("Unable to resolve IMDb id for TMDB id {}: {}", item.id, e),
+        }
+    }
+
+    resolved.sort_by_key(|(position, _)| *position);
+    Ok(resolved.into_iter().map(|(_, meta)| meta).collect())
+}
+
+fn catalog_meta(
+    item: TmdbItem,
+    imdb_id: String,
+    catalog_type: ContentType,
+    genres: &[TmdbGenre],
+) -> CatalogMeta {
+    let name = item.title.or(item.name).unwrap_or_default();
+
+    let genre_names: Vec<String> = item
+        .genre_ids
+        .iter()
+        .flatten()
+        .filter_map(|genre_id| genres.iter().find(|genre| genre.id == *genre_id))
+        .map(|genre| genre.name.clone())
+        .collect();
+
+    let released = item.release_date.or(item.first_air_date);
+    let release_info = released.and_then(|date| date.get(..4).map(|year| year.to_string()));
+
+    let poster = item
+        .poster_path
+        .map(|path| format!("{}/w500{}", TMDB_IMAGE_URL, path));
+    let background = item
+        .backdrop_path
+        .map(|path| format!("{}/w1280{}", TMDB_IMAGE_URL, path));
+    let logo = format!("https://images.metahub.space/logo/medium/{}/img", imdb_id);
+
+    let behavior_hints = DefaultVideoID {
+        default_video_id: imdb_id.clone(),
+    };
+
+    CatalogMeta {
+        id: imdb_id,
+        name,
+        catalog_type,
+        genres: (!genre_names.is_empty()).then_some(genre_names),
+        release_info,
+        background,
+        poster,
+        description: item.overview,
+        behavior_hints: Some(behavior_hints),
+        trailer: None,
+        logo: Some(logo),
+        runtime: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn with_genres(genres: Option<&str>, genre_id: Option<u32>) -> Option<String> {
+        let catalog: TmdbCatalog = serde_json::from_value(json!({
+            "endpoint": "Discover",
+            "catalog_type": "movie",
+            "genres": genres,
+        }))
+        .unwrap();
+
+        let mut url = Url::parse("https://api.themoviedb.org/3/discover/movie").unwrap();
+        catalog.append_discover_filters(&mut url, genre_id);
+        url.query_pairs()
+            .find(|(name, _)| name == "with_genres")
+            .map(|(_, value)| value.into_owned())
+    }
+
+    #[test]
+    fn requested_genre_narrows_configured_genres() {
+        assert_eq!(
+            with_genres(Some("28|12"), Some(35)).as_deref(),
+            Some("28|12,35")
+        );
+        assert_eq!(with_genres(Some("28"), None).as_deref(), Some("28"));
+        assert_eq!(with_genres(None, Some(35)).as_deref(), Some("35"));
+        assert_eq!(with_genres(None, None), None);
+    }
+}
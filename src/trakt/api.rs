@@ -5,7 +5,7 @@ use serde::Deserialize;
 pub struct Ids {
     pub trakt: u32,
     pub slug: String,
-    pub imdb: String,
+    pub imdb: Option<String>,
     pub tmdb: Option<u32>,
     pub tvdb: Option<u32>,
     pub tvrage: Option<u32>,
@@ -94,3 +94,255 @@ pub enum TraktItem {
         show: TraktShow,
     },
 }
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TraktTrendingItem {
+    Movie { watchers: u32, movie: TraktMovie },
+    Show { watchers: u32, show: TraktShow },
+}
+
+/// A movie or show pulled out of one of Trakt's response wrappers
+#[derive(Debug)]
+pub enum TraktMedia {
+    Movie(TraktMovie),
+    Show(TraktShow),
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TraktSearchItem {
+    Movie {
+        score: Option<f64>,
+        movie: TraktMovie,
+    },
+    Show {
+        score: Option<f64>,
+        show: TraktShow,
+    },
+}
+
+impl From<TraktSearchItem> for TraktMedia {
+    fn from(item: TraktSearchItem) -> Self {
+        match item {
+            TraktSearchItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktSearchItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+impl From<TraktItem> for TraktMedia {
+    fn from(item: TraktItem) -> Self {
+        match item {
+            TraktItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+impl From<TraktTrendingItem> for TraktMedia {
+    fn from(item: TraktTrendingItem) -> Self {
+        match item {
+            TraktTrendingItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktTrendingItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TraktAnticipatedItem {
+    Movie { list_count: u32, movie: TraktMovie },
+    Show { list_count: u32, show: TraktShow },
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TraktBoxOfficeItem {
+    pub revenue: u64,
+    pub movie: TraktMovie,
+}
+
+// Shared shape of the most watched, most played and most collected endpoints
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TraktStatsItem {
+    Movie {
+        watcher_count: Option<u32>,
+        play_count: Option<u32>,
+        collected_count: Option<u32>,
+        collector_count: Option<u32>,
+        movie: TraktMovie,
+    },
+    Show {
+        watcher_count: Option<u32>,
+        play_count: Option<u32>,
+        collected_count: Option<u32>,
+        collector_count: Option<u32>,
+        show: TraktShow,
+    },
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TraktFavoritedItem {
+    Movie { user_count: u32, movie: TraktMovie },
+    Show { user_count: u32, show: TraktShow },
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TraktUpdatedItem {
+    Movie {
+        updated_at: String, // DateTime as String
+        movie: TraktMovie,
+    },
+    Show {
+        updated_at: String, // DateTime as String
+        show: TraktShow,
+    },
+}
+
+impl From<TraktAnticipatedItem> for TraktMedia {
+    fn from(item: TraktAnticipatedItem) -> Self {
+        match item {
+            TraktAnticipatedItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktAnticipatedItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+impl From<TraktBoxOfficeItem> for TraktMedia {
+    fn from(item: TraktBoxOfficeItem) -> Self {
+        TraktMedia::Movie(item.movie)
+    }
+}
+
+impl From<TraktStatsItem> for TraktMedia {
+    fn from(item: TraktStatsItem) -> Self {
+        match item {
+            TraktStatsItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktStatsItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+impl From<TraktFavoritedItem> for TraktMedia {
+    fn from(item: TraktFavoritedItem) -> Self {
+        match item {
+            TraktFavoritedItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktFavoritedItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+impl From<TraktUpdatedItem> for TraktMedia {
+    fn from(item: TraktUpdatedItem) -> Self {
+        match item {
+            TraktUpdatedItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktUpdatedItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TraktWatchedItem {
+    Movie {
+        plays: u32,
+        last_watched_at: Option<String>, // DateTime as String
+        movie: TraktMovie,
+    },
+    Show {
+        plays: u32,
+        last_watched_at: Option<String>, // DateTime as String
+        show: TraktShow,
+    },
+}
+
+impl From<TraktWatchedItem> for TraktMedia {
+    fn from(item: TraktWatchedItem) -> Self {
+        match item {
+            TraktWatchedItem::Movie { movie, .. } => TraktMedia::Movie(movie),
+            TraktWatchedItem::Show { show, .. } => TraktMedia::Show(show),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TraktListIds {
+    pub trakt: u64,
+    pub slug: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TraktList {
+    pub name: String,
+    pub description: Option<String>,
+    pub privacy: Option<String>,
+    pub item_count: Option<u32>,
+    pub ids: TraktListIds,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TraktPerson {
+    pub name: String,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TraktCastMember {
+    pub characters: Option<Vec<String>>,
+    pub person: TraktPerson,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TraktCrewMember {
+    pub jobs: Option<Vec<String>>,
+    pub person: TraktPerson,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct TraktCrew {
+    #[serde(default)]
+    pub directing: Vec<TraktCrewMember>,
+    #[serde(default)]
+    pub writing: Vec<TraktCrewMember>,
+}
+
+// `/movies/{id}/people` and `/shows/{id}/people`
+#[derive(Deserialize, Debug, Default)]
+pub struct TraktPeople {
+    #[serde(default)]
+    pub cast: Vec<TraktCastMember>,
+    #[serde(default)]
+    pub crew: TraktCrew,
+}
+
+// `/shows/{id}/seasons?extended=episodes`, season 0 holds the specials
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TraktSeason {
+    pub number: u32,
+    #[serde(default)]
+    pub episodes: Vec<TraktEpisode>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct TraktEpisode {
+    pub season: u32,
+    pub number: u32,
+    pub title: Option<String>,
+    pub overview: Option<String>,
+    pub first_aired: Option<String>, // DateTime as String
+}
@@ -0,0 +1,220 @@
+use super::TRAKT_API_URL;
+use crate::{
+    globals::{Environment, GlobalClient},
+    uploads::UploadStore,
+};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+// Refresh tokens a little before they actually expire so in-flight requests don't fail
+const EXPIRY_MARGIN_SECS: u64 = 300;
+// Rotated tokens are stored with the uploads, under the hash of the config's refresh token
+const TOKEN_KIND: &str = "trakt_tokens";
+
+type LatestTokens = Arc<Mutex<Option<TraktAuth>>>;
+
+// Trakt's refresh tokens are single use and rotate, but the config stored in the Stremio install
+// URL can't be rewritten. The latest tokens for a config are kept here and on disk, so they
+// outlive a restart. Each config has a lock of its own, held while its tokens are refreshed.
+static REFRESHED_TOKENS: Lazy<Mutex<HashMap<String, LatestTokens>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// User tokens embedded in the catalog config for endpoints that require OAuth
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraktAuth {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix timestamp (seconds)
+    pub expires_at: u64,
+}
+
+impl TraktAuth {
+    fn is_expired(&self) -> bool {
+        unix_now() + EXPIRY_MARGIN_SECS >= self.expires_at
+    }
+
+    /// Returns a usable set of tokens, refreshing them with Trakt if they have expired. Once
+    /// Trakt turns the refresh token down the user has to connect Trakt again and reinstall.
+    pub async fn refreshed(&self) -> Result<TraktAuth> {
+        let key = format!("{:x}", Sha256::digest(self.refresh_token.as_bytes()));
+
+        let latest_tokens = REFRESHED_TOKENS
+            .lock()
+            .await
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let mut latest_tokens = latest_tokens.lock().await;
+
+        if latest_tokens.is_none() {
+            *latest_tokens = UploadStore::load::<TraktAuth>(TOKEN_KIND, &key)
+                .await
+                .ok()
+                .map(|stored| stored.as_ref().clone());
+        }
+
+        let current = latest_tokens.clone().unwrap_or_else(|| self.clone());
+        if !current.is_expired() {
+            return Ok(current);
+        }
+
+        let refreshed = refresh_token(&current.refresh_token)
+            .await
+            .context("Trakt sign-in has expired, connect Trakt again and reinstall the addon")?;
+
+        // The old refresh token is spent, losing the new one would lock the user out
+        if let Err(e) = UploadStore::replace(TOKEN_KIND, &key, refreshed.clone()).await {
+            println!("Unable to store refreshed Trakt tokens: {}", e);
+        }
+        *latest_tokens = Some(refreshed.clone());
+        Ok(refreshed)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraktDeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct TraktTokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: u64,
+    refresh_token: String,
+    scope: String,
+    created_at: u64,
+}
+
+impl From<TraktTokenResponse> for TraktAuth {
+    fn from(response: TraktTokenResponse) -> Self {
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            expires_at: response.created_at + response.expires_in,
+        }
+    }
+}
+
+/// State of a device-code authorization, sent back to the configure dashboard while polling
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TraktDeviceStatus {
+    Pending,
+    SlowDown,
+    Authorized { auth: TraktAuth },
+    Invalid,
+    AlreadyUsed,
+    Expired,
+    Denied,
+}
+
+/// Starts the device-code flow, the user enters `user_code` at `verification_url`
+pub async fn request_device_code() -> Result<TraktDeviceCode> {
+    let env = Environment::get().context("Unable to get global Environment for Trakt auth")?;
+    let client = GlobalClient::get()?;
+
+    let response = client
+        .post(format!("{}/oauth/device/code", TRAKT_API_URL))
+        .json(&json!({ "client_id": env.trakt_client_id }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let device_code: TraktDeviceCode = response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert Trakt device code response to json: {}",
+            e.to_string()
+        )
+    })?;
+
+    Ok(device_code)
+}
+
+/// Checks whether the user has approved the device code yet
+pub async fn poll_device_token(device_code: &str) -> Result<TraktDeviceStatus> {
+    let env = Environment::get().context("Unable to get global Environment for Trakt auth")?;
+    let client = GlobalClient::get()?;
+
+    let response = client
+        .post(format!("{}/oauth/device/token", TRAKT_API_URL))
+        .json(&json!({
+            "code": device_code,
+            "client_id": env.trakt_client_id,
+            "client_secret": env.trakt_client_secret,
+        }))
+        .send()
+        .await?;
+
+    // Status codes as documented by Trakt for the device token endpoint
+    let status = match response.status() {
+        StatusCode::OK => {
+            let tokens: TraktTokenResponse = response.json().await.map_err(|e| {
+                anyhow!(
+                    "Unable to convert Trakt token response to json: {}",
+                    e.to_string()
+                )
+            })?;
+            TraktDeviceStatus::Authorized {
+                auth: tokens.into(),
+            }
+        }
+        StatusCode::BAD_REQUEST => TraktDeviceStatus::Pending,
+        StatusCode::NOT_FOUND => TraktDeviceStatus::Invalid,
+        StatusCode::CONFLICT => TraktDeviceStatus::AlreadyUsed,
+        StatusCode::GONE => TraktDeviceStatus::Expired,
+        StatusCode::IM_A_TEAPOT => TraktDeviceStatus::Denied,
+        StatusCode::TOO_MANY_REQUESTS => TraktDeviceStatus::SlowDown,
+        other => return Err(anyhow!("Unexpected Trakt device token status: {}", other)),
+    };
+
+    Ok(status)
+}
+
+async fn refresh_token(refresh_token: &str) -> Result<TraktAuth> {
+    let env = Environment::get().context("Unable to get global Environment for Trakt auth")?;
+    let client = GlobalClient::get()?;
+
+    let response = client
+        .post(format!("{}/oauth/token", TRAKT_API_URL))
+        .json(&json!({
+            "refresh_token": refresh_token,
+            "client_id": env.trakt_client_id,
+            "client_secret": env.trakt_client_secret,
+            "redirect_uri": "urn:ietf:wg:oauth:2.0:oob",
+            "grant_type": "refresh_token",
+        }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Trakt rejected the refresh token")?;
+
+    let tokens: TraktTokenResponse = response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert Trakt token response to json: {}",
+            e.to_string()
+        )
+    })?;
+
+    Ok(tokens.into())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,360 @@
+use super::{
+    api::{Ids, TraktCrewMember, TraktMovie, TraktPeople, TraktSearchItem, TraktSeason, TraktShow},
+    extract_video_id, genres, trakt_headers, trakt_media_kind, TRAKT_API_URL,
+};
+use crate::{
+    addon::{
+        catalog::{CatalogType, ContentType, DefaultVideoID, Trailer},
+        meta::{MetaDetail, MetaLink, MetaResponse, Video},
+    },
+    globals::GlobalClient,
+    mapping::{ExternalIds, IdMapping},
+};
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+
+// Trakt credits everyone, the detail page only lists the top billed
+const MAX_CAST: usize = 10;
+
+/// Fields movies and shows share, borrowed from either
+struct TitleDetails<'a> {
+    ids: &'a Ids,
+    title: &'a str,
+    overview: Option<&'a String>,
+    genres: Option<&'a Vec<String>>,
+    runtime: Option<u32>,
+    certification: Option<&'a String>,
+    country: Option<&'a String>,
+    language: Option<&'a String>,
+    homepage: Option<&'a String>,
+    trailer: Option<&'a String>,
+}
+
+impl<'a> From<&'a TraktMovie> for TitleDetails<'a> {
+    fn from(movie: &'a TraktMovie) -> Self {
+        Self {
+            ids: &movie.ids,
+            title: &movie.title,
+            overview: movie.overview.as_ref(),
+            genres: movie.genres.as_ref(),
+            runtime: movie.runtime,
+            certification: movie.certification.as_ref(),
+            country: movie.country.as_ref(),
+            language: movie.language.as_ref(),
+            homepage: movie.homepage.as_ref(),
+            trailer: movie.trailer.as_ref(),
+        }
+    }
+}
+
+impl<'a> From<&'a TraktShow> for TitleDetails<'a> {
+    fn from(show: &'a TraktShow) -> Self {
+        Self {
+            ids: &show.ids,
+            title: &show.title,
+            overview: show.overview.as_ref(),
+            genres: show.genres.as_ref(),
+            runtime: show.runtime,
+            certification: show.certification.as_ref(),
+            country: show.country.as_ref(),
+            language: show.language.as_ref(),
+            homepage: show.homepage.as_ref(),
+            trailer: show.trailer.as_ref(),
+        }
+    }
+}
+
+/// Full meta for a movie or show by the id Stremio knows it under, `tt…` or `kitsu:…`. The
+/// meta keeps the type it was asked for, e.g. anime, while Trakt is asked for the show.
+pub async fn meta(content_type: &ContentType, id: &str) -> Result<MetaResponse> {
+    let catalog_type = content_type.media_kind();
+    // Kitsu splits a show into an entry per season while Trakt has the whole show, its episodes
+    // can't be numbered the way kitsu ids expect. Failing lets Stremio ask the next addon.
+    if catalog_type == CatalogType::Series && id.starts_with("kitsu:") {
+        return Err(anyhow!("Episodes for kitsu series {} can't be served", id));
+    }
+    let trakt_id = trakt_id(catalog_type, id).await?;
+    let kind = trakt_media_kind(catalog_type);
+    let title_path = [kind, trakt_id.as_str()];
+    let extended_full = [("extended", "full")];
+
+    // Credits are nice to have, the title itself is not
+    let people = async {
+        get_json::<TraktPeople>(&[kind, &trakt_id, "people"], &[])
+            .await
+            .map_err(|e| println!("Unable to fetch Trakt people for {}: {}", id, e))
+            .unwrap_or_default()
+    };
+
+    let meta = match catalog_type {
+        CatalogType::Movie => {
+            let (movie, people) =
+                tokio::join!(get_json::<TraktMovie>(&title_path, &extended_full), people);
+            let movie = movie?;
+
+            let mut meta = title_meta(id, content_type, TitleDetails::from(&movie), people).await;
+            meta.release_info = movie.year.map(|year| year.to_string());
+            meta.released = movie
+                .released
+                .as_ref()
+                .map(|date| format!("{}T00:00:00.000Z", date));
+            meta.behavior_hints = Some(DefaultVideoID {
+                default_video_id: id.to_string(),
+            });
+            meta
+        }
+        CatalogType::Series => {
+            let seasons_path = [kind, trakt_id.as_str(), "seasons"];
+            let (show, people, seasons) = tokio::join!(
+                get_json::<TraktShow>(&title_path, &extended_full),
+                people,
+                get_json::<Vec<TraktSeason>>(&seasons_path, &[("extended", "full,episodes")])
+            );
+            let show = show?;
+            let seasons = seasons?;
+
+            let mut meta = title_meta(id, content_type, TitleDetails::from(&show), people).await;
+            meta.release_info = show_release_info(&show, &seasons);
+            meta.released = show.first_aired.clone();
+            meta.videos = videos(id, seasons);
+            meta
+        }
+    };
+
+    Ok(MetaResponse { meta })
+}
+
+async fn title_meta(
+    id: &str,
+    content_type: &ContentType,
+    details: TitleDetails<'_>,
+    people: TraktPeople,
+) -> MetaDetail {
+    let catalog_type = content_type.media_kind();
+    let mut meta = MetaDetail::from_id(
+        id.to_string(),
+        details.title.to_string(),
+        content_type.clone(),
+    );
+
+    meta.description = details.overview.cloned();
+    meta.genres = genre_names(catalog_type, details.genres).await;
+    meta.runtime = details.runtime.map(|runtime| format!("{} min", runtime));
+    meta.country = details.country.map(|country| country.to_uppercase());
+    meta.language = details.language.cloned();
+    meta.website = details.homepage.cloned();
+
+    meta.trailers = details
+        .trailer
+        .and_then(|trailer| extract_video_id(trailer).ok())
+        .map(|code| Trailer {
+            source: code.to_string(),
+            trailer_type: "Trailer".to_string(),
+        })
+        .into_iter()
+        .collect();
+
+    meta.cast = people
+        .cast
+        .into_iter()
+        .take(MAX_CAST)
+        .map(|member| member.person.name)
+        .collect();
+    meta.director = crew_names(&people.crew.directing, "Director");
+    meta.writer = crew_names(&people.crew.writing, "Writer");
+
+    let trakt_url = format!(
+        "https://trakt.tv/{}/{}",
+        trakt_media_kind(catalog_type),
+        details.ids.slug
+    );
+
+    if let Some(imdb_id) = &details.ids.imdb {
+        meta.links.push(MetaLink::new(
+            "IMDb",
+            "imdb",
+            format!("https://imdb.com/title/{}", imdb_id),
+        ));
+    }
+    if let Some(certification) = details.certification {
+        meta.links.push(MetaLink::new(
+            certification,
+            "Certification",
+            trakt_url.clone(),
+        ));
+    }
+    let people_links: Vec<MetaLink> = [
+        (&meta.cast, "Cast"),
+        (&meta.director, "Directors"),
+        (&meta.writer, "Writers"),
+    ]
+    .into_iter()
+    .flat_map(|(names, category)| {
+        names
+            .iter()
+            .map(move |name| MetaLink::new(name, category, search_url(name)))
+    })
+    .collect();
+    meta.links.extend(people_links);
+    meta.links
+        .push(MetaLink::new(details.title, "share", trakt_url));
+
+    meta
+}
+
+// Finds the Trakt id to look a title up by. Trakt takes IMDb ids directly, kitsu ids go
+// through the mapping file to an id Trakt knows.
+async fn trakt_id(catalog_type: CatalogType, id: &str) -> Result<String> {
+    if id.starts_with("tt") {
+        return Ok(id.to_string());
+    }
+
+    let kitsu_id: u64 = id
+        .strip_prefix("kitsu:")
+        .and_then(|kitsu_id| kitsu_id.parse().ok())
+        .with_context(|| format!("Unsupported meta id {}", id))?;
+
+    let mapping = IdMapping::get()?;
+    let entry = mapping
+        .find(&ExternalIds {
+            kitsu: Some(kitsu_id),
+            ..ExternalIds::default()
+        })
+        .with_context(|| format!("No known ids for {}", id))?;
+
+    if let Some(imdb_id) = &entry.imdb_id {
+        return Ok(imdb_id.clone());
+    }
+
+    let (id_type, external_id) = match (entry.thetvdb_id, entry.themoviedb_id) {
+        (Some(tvdb_id), _) => ("tvdb", tvdb_id),
+        (None, Some(tmdb_id)) => ("tmdb", tmdb_id),
+        (None, None) => return Err(anyhow!("No Trakt compatible ids for {}", id)),
+    };
+
+    let search_type = match catalog_type {
+        CatalogType::Movie => "movie",
+        CatalogType::Series => "show",
+    };
+    let results: Vec<TraktSearchItem> = get_json(
+        &["search", id_type, &external_id.to_string()],
+        &[("type", search_type)],
+    )
+    .await?;
+
+    results
+        .into_iter()
+        .next()
+        .map(|result| match result {
+            TraktSearchItem::Movie { movie, .. } => movie.ids.trakt.to_string(),
+            TraktSearchItem::Show { show, .. } => show.ids.trakt.to_string(),
+        })
+        .with_context(|| format!("Trakt has no title for {}", id))
+}
+
+// Trakt tags titles by slug, the names come from its genre list
+async fn genre_names(catalog_type: CatalogType, slugs: Option<&Vec<String>>) -> Vec<String> {
+    let Some(slugs) = slugs else {
+        return Vec::new();
+    };
+    let known_genres = genres(catalog_type).await.ok();
+
+    slugs
+        .iter()
+        .map(|slug| {
+            known_genres
+                .iter()
+                .flat_map(|genres| genres.iter())
+                .find(|genre| &genre.slug == slug)
+                .map(|genre| genre.name.clone())
+                .unwrap_or_else(|| slug.clone())
+        })
+        .collect()
+}
+
+fn crew_names(crew: &[TraktCrewMember], job: &str) -> Vec<String> {
+    crew.iter()
+        .filter(|member| {
+            member
+                .jobs
+                .as_ref()
+                .is_some_and(|jobs| jobs.iter().any(|member_job| member_job == job))
+        })
+        .map(|member| member.person.name.clone())
+        .collect()
+}
+
+fn search_url(query: &str) -> String {
+    Url::parse_with_params("stremio:///search", [("search", query)])
+        .map(|url| url.to_string())
+        .unwrap_or_default()
+}
+
+// e.g. "2008-2013" for an ended show, "2019-" for one still running
+fn show_release_info(show: &TraktShow, seasons: &[TraktSeason]) -> Option<String> {
+    let first_year = show.year?;
+
+    let running = !matches!(show.status.as_deref(), Some("ended" | "canceled"));
+    if running {
+        return Some(format!("{}-", first_year));
+    }
+
+    let last_year = seasons
+        .iter()
+        .flat_map(|season| season.episodes.iter())
+        .filter_map(|episode| episode.first_aired.as_ref()?.get(..4)?.parse::<u32>().ok())
+        .max();
+
+    match last_year {
+        Some(last_year) if last_year > first_year => Some(format!("{}-{}", first_year, last_year)),
+        _ => Some(first_year.to_string()),
+    }
+}
+
+fn videos(id: &str, seasons: Vec<TraktSeason>) -> Vec<Video> {
+    seasons
+        .into_iter()
+        .flat_map(|season| season.episodes)
+        .map(|episode| Video {
+            id: format!("{}:{}:{}", id, episode.season, episode.number),
+            title: episode
+                .title
+                .unwrap_or_else(|| format!("Episode {}", episode.number)),
+            released: episode.first_aired,
+            season: episode.season,
+            episode: episode.number,
+            overview: episode.overview,
+        })
+        .collect()
+}
+
+async fn get_json<T: DeserializeOwned>(
+    path_segments: &[&str],
+    query: &[(&str, &str)],
+) -> Result<T> {
+    let client = GlobalClient::get()?;
+    let headers = trakt_headers(&None).await?;
+
+    let mut url = Url::parse(TRAKT_API_URL)?;
+    url.path_segments_mut()
+        .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+        .extend(path_segments);
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+
+    let response = client
+        .get(url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert TraktAPI response to json: {}",
+            e.to_string()
+        )
+    })
+}
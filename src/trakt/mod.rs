@@ -1,21 +1,63 @@
 use crate::{
     addon::catalog::{
-        CatalogMeta, CatalogRequestParams, CatalogResponse, CatalogType, DefaultVideoID,
-        PaginationDetails, Trailer,
+        decode_config, encode_path_value, CatalogMeta, CatalogRequestParams, CatalogResponse,
+        CatalogType, ContentType, Trailer,
     },
+    genres::{has_genre, slug},
     globals::{Environment, GlobalClient},
+    mapping::{ExternalIds, IdMapping},
 };
 use anyhow::{anyhow, Context, Result};
-use api::TraktItem;
+use api::{
+    Ids, TraktAnticipatedItem, TraktBoxOfficeItem, TraktFavoritedItem, TraktItem, TraktMedia,
+    TraktMovie, TraktSearchItem, TraktShow, TraktStatsItem, TraktTrendingItem, TraktUpdatedItem,
+};
+use auth::TraktAuth;
 use axum::http::HeaderMap;
-use base64::{engine::general_purpose::STANDARD, Engine};
+use once_cell::sync::Lazy;
 use reqwest::Url;
-use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_value, Value};
+use std::collections::HashMap;
 use std::fmt::{self, Display};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use watched::watched_ids;
 
 pub mod api;
+pub mod auth;
+pub mod lists;
+pub mod meta;
+pub mod watched;
+
+// Upper bound on Trakt pages walked to fill a single page when items are filtered locally
+const MAX_FILTERED_PAGE_SCAN: i32 = 10;
+// How long a filtered scan is kept for the following pages to carry on from
+const FILTERED_SCAN_TTL: Duration = Duration::from_secs(10 * 60);
+
+pub const TRAKT_API_URL: &str = "https://api.trakt.tv";
+
+// Trakt's genre list hardly ever changes, it is fetched once per type for the life of the process
+static GENRES: Lazy<Mutex<HashMap<CatalogType, Arc<Vec<TraktGenre>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Items a filtered catalog has kept so far and how far into Trakt's pages it got
+#[derive(Default)]
+struct FilteredScan {
+    kept: Vec<CatalogMeta>,
+    pages_scanned: i32,
+    exhausted: bool,
+}
+
+struct FilteredScanEntry {
+    started_at: Instant,
+    scan: Arc<Mutex<FilteredScan>>,
+}
+
+// Keyed by the catalog config, genre and search query, see TraktCatalog::scan_key
+static FILTERED_SCANS: Lazy<Mutex<HashMap<String, FilteredScanEntry>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TraktCatalog {
@@ -23,8 +65,23 @@ pub struct TraktCatalog {
     pagination: Option<TraktPagination>,
     extended_info: bool,
     list_id: Option<String>,
-    catalog_type: CatalogType,
+    catalog_type: ContentType,
     genre: Option<String>,
+    period: Option<TraktPeriod>,
+    limit: Option<i32>,
+    auth: Option<TraktAuth>,
+    user_id: Option<String>,
+    sort: Option<TraktWatchlistSort>,
+    sort_direction: Option<TraktSortDirection>,
+    ignore_collected: Option<bool>,
+    ignore_watchlisted: Option<bool>,
+    hide_watched: Option<bool>,
+    // Query from Stremio's search extra, never part of the stored config
+    #[serde(skip)]
+    search: Option<String>,
+    // Config, genre and search query of the request, filtered scans are shared under it
+    #[serde(skip)]
+    scan_key: Option<String>,
 }
 
 impl TraktCatalog {
@@ -70,33 +127,34 @@ impl TraktCatalog {
         // Our catalog struct has been stored in the "catalog_id" catalog parameter provided by
         // Stremio on the request to the catalog endpoint, we will decode and convert to struct
 
-        let catalog_id_decoded = STANDARD
-            .decode(&catalog_request_params.catalog_id)
-            .map_err(|e| {
-                anyhow!(
-                    "from_b64: Error decoding TraktCatalog from b64 to a vec: {}",
-                    e.to_string()
-                )
-            })?;
-
-        let catalog_id_decoded_str = String::from_utf8(catalog_id_decoded).map_err(|e| {
-            anyhow!(
-                "from_b64: Error converting decoded b64 value to json string: {}",
-                e.to_string()
-            )
-        })?;
-
-        println!("catalog_id_decoded_str: {catalog_id_decoded_str}");
-
         let mut trakt_catalog_from_catalog_id: TraktCatalog =
-            serde_json::from_str(catalog_id_decoded_str.as_str()).map_err(|e| {
-                anyhow!(
-                    "from_b64: Error converting decoded json string to TraktCatalog struct: {}",
-                    e.to_string()
-                )
-            })?;
-
-        trakt_catalog_from_catalog_id.handle_pagination_based_on_genre(catalog_request_params);
+            decode_config(&catalog_request_params.catalog_id)?;
+
+        trakt_catalog_from_catalog_id.pagination(
+            catalog_request_params.pagination.page,
+            catalog_request_params.pagination.page_size,
+        );
+        trakt_catalog_from_catalog_id.search = catalog_request_params.search.clone();
+        // Encoded like a catalog path, so no genre or query can run into the next part
+        trakt_catalog_from_catalog_id.scan_key = Some(format!(
+            "{}/genre={}&search={}",
+            encode_path_value(&catalog_request_params.catalog_id),
+            encode_path_value(catalog_request_params.genre.as_deref().unwrap_or_default()),
+            encode_path_value(catalog_request_params.search.as_deref().unwrap_or_default())
+        ));
+
+        // Stremio's genre extra takes over from a genre fixed in the config
+        if let Some(genre) = &catalog_request_params.genre {
+            trakt_catalog_from_catalog_id.genre = Some(genre.clone());
+        }
+        if let Some(genre) = &trakt_catalog_from_catalog_id.genre {
+            let genre_slug = genre_slug(
+                trakt_catalog_from_catalog_id.catalog_type.media_kind(),
+                genre,
+            )
+            .await;
+            trakt_catalog_from_catalog_id.genre = Some(genre_slug);
+        }
 
         let trakt_response = trakt_catalog_from_catalog_id.build().await.map_err(|e| {
             anyhow!(
@@ -116,59 +174,209 @@ impl TraktCatalog {
         }
     }
 
-    pub fn handle_pagination_based_on_genre(
-        &mut self,
-        catalog_path_options: &CatalogRequestParams,
-    ) -> &mut Self {
-        // When the endpoint is TraktEndpoint::List and a genre is selected, since Trakt's API does
-        // not allow sorting via parameters, instead 500 items are pulled on the first page's
-        // requests. Any additional page requests are set to empty responses in the build function
-        // to avoid sending duplicative catalogs
-        let pagination = {
-            if let TraktEndpoint::List = self.endpoint {
-                if catalog_path_options.genre.is_some() {
-                    PaginationDetails {
-                        page: catalog_path_options.pagination.page,
-                        page_size: 500,
-                    }
-                } else {
-                    PaginationDetails {
-                        page: catalog_path_options.pagination.page,
-                        page_size: catalog_path_options.pagination.page_size,
-                    }
-                }
-            } else {
-                PaginationDetails {
-                    page: catalog_path_options.pagination.page,
-                    page_size: catalog_path_options.pagination.page_size,
-                }
+    pub async fn build(&self) -> Result<TraktResponse> {
+        let (current_page, items_per_page) = match &self.pagination {
+            Some(pagination_details) => (
+                pagination_details.current_page,
+                pagination_details.items_per_page,
+            ),
+            None => (1, 100),
+        };
+
+        // Items already served on previous pages, used to cap the catalog at the configured limit
+        let offset = (current_page - 1) * items_per_page;
+
+        if let Some(limit) = self.limit {
+            if offset >= limit {
+                let empty_response = TraktResponse::CatalogResponse(CatalogResponse::new_empty());
+                return Ok(empty_response);
             }
+        }
+
+        // Unpaginated endpoints hand back the same items for every page, so any additional page
+        // requests get an empty response
+        if !self.endpoint.is_paginated() && current_page > 1 {
+            let empty_response = TraktResponse::CatalogResponse(CatalogResponse::new_empty());
+            return Ok(empty_response);
+        }
+
+        let mut catalog_response = if self.filters_locally() {
+            self.fetch_filtered(current_page, items_per_page).await?
+        } else {
+            self.fetch_page(current_page, items_per_page).await?.0
         };
-        self.pagination(pagination.page, pagination.page_size);
-        self
+
+        if let Some(limit) = self.limit {
+            catalog_response.metas.truncate((limit - offset) as usize);
+        }
+
+        Ok(TraktResponse::CatalogResponse(catalog_response))
     }
 
-    pub async fn build(&self) -> Result<TraktResponse> {
-        let env = Environment::get().context("Unable to get global Environment for Trakt query")?;
-        let client = GlobalClient::get()?;
+    // Genres are only filtered on Trakt's side for endpoints that take filters
+    fn filters_genre_locally(&self) -> bool {
+        self.genre.is_some() && !self.endpoint.takes_filters()
+    }
+
+    fn filters_locally(&self) -> bool {
+        self.hide_watched.unwrap_or(false) || self.filters_genre_locally()
+    }
+
+    // Watched items and, on endpoints without filters, other genres are removed after parsing,
+    // so Trakt pages are walked until enough items have been kept to fill the requested page.
+    // The scan is kept, a later page carries on from where the previous one stopped.
+    async fn fetch_filtered(
+        &self,
+        current_page: i32,
+        items_per_page: i32,
+    ) -> Result<CatalogResponse> {
+        let watched = if self.hide_watched.unwrap_or(false) {
+            let auth = self
+                .auth
+                .as_ref()
+                .context("Hiding watched items requires an authenticated user")?;
+            Some(watched_ids(auth, self.catalog_type.media_kind()).await?)
+        } else {
+            None
+        };
+        let genre = self
+            .genre
+            .as_deref()
+            .filter(|_| self.filters_genre_locally());
 
-        let mut headers = HeaderMap::new();
-        // Required Trakt API headers
-        headers.insert("Content-Type", "application/json".parse()?);
-        headers.insert("trakt-api-key", env.trakt_client_id.parse()?);
-        headers.insert("trakt-api-version", "2".parse()?);
+        let offset = ((current_page - 1) * items_per_page) as usize;
+        let wanted = offset + items_per_page as usize;
 
-        let mut url = Url::parse("https://api.trakt.tv")?;
+        let scan = self.filtered_scan().await;
+        let mut scan = scan.lock().await;
 
-        // Convert catalog type to valid string for Trakt API
-        let trakt_catalog_type = match self.catalog_type {
-            CatalogType::Movie => "movies",
-            CatalogType::Series => "shows",
+        for _ in 0..MAX_FILTERED_PAGE_SCAN {
+            if scan.kept.len() >= wanted || scan.exhausted {
+                break;
+            }
+
+            let (page_response, exhausted) = self
+                .fetch_page(scan.pages_scanned + 1, items_per_page)
+                .await?;
+            scan.pages_scanned += 1;
+            scan.exhausted = exhausted || !self.endpoint.is_paginated();
+
+            scan.kept
+                .extend(page_response.metas.into_iter().filter(|meta| {
+                    let unwatched = watched
+                        .as_ref()
+                        .is_none_or(|watched| !watched.contains(&meta.id));
+                    let in_genre = genre.is_none_or(|genre| {
+                        meta.genres
+                            .as_deref()
+                            .is_some_and(|genres| has_genre(genres, genre))
+                    });
+                    unwatched && in_genre
+                }));
+        }
+
+        let mut catalog_response = CatalogResponse::new_empty();
+        catalog_response.metas = scan
+            .kept
+            .iter()
+            .skip(offset)
+            .take(items_per_page as usize)
+            .cloned()
+            .collect();
+        Ok(catalog_response)
+    }
+
+    // Scans are only shared between requests for the same config, genre and search, expired ones
+    // start over
+    async fn filtered_scan(&self) -> Arc<Mutex<FilteredScan>> {
+        let Some(scan_key) = &self.scan_key else {
+            return Arc::new(Mutex::new(FilteredScan::default()));
         };
 
+        let mut scans = FILTERED_SCANS.lock().await;
+        if let Some(entry) = scans.get(scan_key) {
+            if entry.started_at.elapsed() < FILTERED_SCAN_TTL {
+                return entry.scan.clone();
+            }
+        }
+
+        scans.retain(|_, entry| entry.started_at.elapsed() < FILTERED_SCAN_TTL);
+        let scan = Arc::new(Mutex::new(FilteredScan::default()));
+        scans.insert(
+            scan_key.clone(),
+            FilteredScanEntry {
+                started_at: Instant::now(),
+                scan: scan.clone(),
+            },
+        );
+        scan
+    }
+
+    // Also says whether Trakt has no pages past this one, told by its pagination header or by
+    // a short page. Decided before items without a usable id are dropped, a page can lose
+    // some of its metas and still not be the last.
+    async fn fetch_page(
+        &self,
+        current_page: i32,
+        items_per_page: i32,
+    ) -> Result<(CatalogResponse, bool)> {
+        let client = GlobalClient::get()?;
+
+        let headers = trakt_headers(&self.auth).await?;
+
+        let mut url = Url::parse(TRAKT_API_URL)?;
+
+        let trakt_catalog_type = trakt_media_kind(self.catalog_type.media_kind());
+
+        let period = self.period.unwrap_or_default().as_str();
+
         // Append query string based on query type
         let endpoint_path_segments = match self.endpoint {
-            TraktEndpoint::TrendingMovies => Ok(vec!["movies", "trending"]),
+            TraktEndpoint::Trending => Ok(vec![trakt_catalog_type, "trending"]),
+            TraktEndpoint::Popular => Ok(vec![trakt_catalog_type, "popular"]),
+            TraktEndpoint::Anticipated => Ok(vec![trakt_catalog_type, "anticipated"]),
+            TraktEndpoint::BoxOffice => match self.catalog_type.media_kind() {
+                CatalogType::Movie => Ok(vec!["movies", "boxoffice"]),
+                CatalogType::Series => {
+                    Err(anyhow!("Trakt Box Office is only available for movies"))
+                }
+            },
+            TraktEndpoint::Watched => Ok(vec![trakt_catalog_type, "watched", period]),
+            TraktEndpoint::Played => Ok(vec![trakt_catalog_type, "played", period]),
+            TraktEndpoint::Collected => Ok(vec![trakt_catalog_type, "collected", period]),
+            TraktEndpoint::Favorited => Ok(vec![trakt_catalog_type, "favorited", period]),
+            TraktEndpoint::Updated => Ok(vec![trakt_catalog_type, "updates"]),
+            TraktEndpoint::Watchlist => {
+                let sort = self.sort.unwrap_or_default().as_str();
+                let sort_direction = self.sort_direction.unwrap_or_default().as_str();
+                // A user id points at someone's public watchlist, otherwise the token owner's
+                match (&self.user_id, &self.auth) {
+                    (Some(user_id), _) => Ok(vec![
+                        "users",
+                        user_id.as_str(),
+                        "watchlist",
+                        trakt_catalog_type,
+                        sort,
+                        sort_direction,
+                    ]),
+                    (None, Some(_)) => Ok(vec![
+                        "sync",
+                        "watchlist",
+                        trakt_catalog_type,
+                        sort,
+                        sort_direction,
+                    ]),
+                    (None, None) => Err(anyhow!(
+                        "Trakt Watchlist requires either a user id or an authenticated user"
+                    )),
+                }
+            }
+            TraktEndpoint::Recommendations => match &self.auth {
+                Some(_) => Ok(vec!["recommendations", trakt_catalog_type]),
+                None => Err(anyhow!(
+                    "Trakt Recommendations requires an authenticated user"
+                )),
+            },
             TraktEndpoint::List => {
                 if let Some(list_id) = &self.list_id {
                     Ok(vec!["lists", list_id.as_str(), "items", trakt_catalog_type])
@@ -176,30 +384,62 @@ impl TraktCatalog {
                     Err(anyhow!("No list provided in Trakt List endpoint"))
                 }
             }
+            // Search is the one endpoint using the singular type
+            TraktEndpoint::Search => match self.catalog_type.media_kind() {
+                CatalogType::Movie => Ok(vec!["search", "movie"]),
+                CatalogType::Series => Ok(vec!["search", "show"]),
+            },
         }?;
 
         url.path_segments_mut()
             .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
             .extend(endpoint_path_segments);
 
-        // Add info level
-        if self.extended_info {
+        // Add info level, genres only come with the full info
+        if self.extended_info || self.genre.is_some() {
             url.query_pairs_mut().append_pair("extended", "full");
         }
 
-        // Add pagination details to query string if provided
-        if let Some(pagination_details) = &self.pagination {
-            url.query_pairs_mut()
-                .append_pair("page", &pagination_details.current_page.to_string())
-                .append_pair("limit", &pagination_details.items_per_page.to_string());
+        if let Some(genre) = &self.genre {
+            if self.endpoint.takes_filters() {
+                url.query_pairs_mut().append_pair("genres", genre);
+            }
+        }
+
+        if let TraktEndpoint::Search = self.endpoint {
+            let query = self
+                .search
+                .as_deref()
+                .context("Trakt Search requires a search query")?;
+            url.query_pairs_mut().append_pair("query", query);
         }
 
+        if let TraktEndpoint::Recommendations = self.endpoint {
+            if let Some(ignore_collected) = self.ignore_collected {
+                url.query_pairs_mut()
+                    .append_pair("ignore_collected", &ignore_collected.to_string());
+            }
+            if let Some(ignore_watchlisted) = self.ignore_watchlisted {
+                url.query_pairs_mut()
+                    .append_pair("ignore_watchlisted", &ignore_watchlisted.to_string());
+            }
+        }
+
+        url.query_pairs_mut()
+            .append_pair("page", &current_page.to_string())
+            .append_pair("limit", &items_per_page.to_string());
+
         let request = client.get(url).headers(headers).build()?;
 
         println!("Final URL: {}", request.url());
 
         let response = client.execute(request).await?;
 
+        let page_count = response
+            .headers()
+            .get("X-Pagination-Page-Count")
+            .and_then(|page_count| page_count.to_str().ok()?.parse::<i32>().ok());
+
         let json: Value = response.json().await.map_err(|e| {
             anyhow!(
                 "Unable to convert TraktAPI response to json: {}",
@@ -207,21 +447,25 @@ impl TraktCatalog {
             )
         })?;
 
+        let exhausted = match page_count {
+            Some(page_count) => current_page >= page_count,
+            None => json
+                .as_array()
+                .is_none_or(|items| items.len() < items_per_page as usize),
+        };
+
         let output = self
             .endpoint
-            .parse_output(json, &self.genre)
+            .parse_output(json, &self.catalog_type)
             .map_err(|e| anyhow!("Unable to parse output from Trakt API: {}", e.to_string()))?;
 
-        // This is a continuation of the logic in self::add_catalog_path_options.
-        // If additional pages are being requested when its on the List endpoint and there is a genre (sorting)
-        // set, send an empty response, first response had 500 to compensate
-        // 01/20/2025 - Change, no longer want genres on Trakt catalogs
-        if matches!(self.endpoint, TraktEndpoint::List) && self.genre.is_some() {
-            let empty_response = TraktResponse::CatalogResponse(CatalogResponse::new_empty());
-            return Ok(empty_response);
+        if let TraktResponse::CatalogResponse(catalog_response) = output {
+            Ok((catalog_response, exhausted))
+        } else {
+            Err(anyhow!(
+                "Expected Trakt Catalog Response and did not find it"
+            ))
         }
-
-        Ok(output)
     }
 }
 
@@ -233,101 +477,256 @@ pub enum TraktResponse {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum TraktEndpoint {
-    TrendingMovies,
+    #[serde(alias = "TrendingMovies")]
+    Trending,
     List,
+    Popular,
+    Anticipated,
+    BoxOffice,
+    Watched,
+    Played,
+    Collected,
+    Favorited,
+    Updated,
+    Watchlist,
+    Recommendations,
+    Search,
 }
 
 impl TraktEndpoint {
-    fn parse_output(&self, data: Value, genre: &Option<String>) -> Result<TraktResponse> {
-        match self {
-            TraktEndpoint::TrendingMovies => {
-                // TODO: Implement Trending movies
-                Ok(TraktResponse::CatalogResponse(CatalogResponse::new_empty()))
-            }
-            TraktEndpoint::List => {
-                let api_data: Vec<TraktItem> = from_value(data.clone())?;
-
-                // Add sorting logic
-                let mut new_catalog_response = CatalogResponse::new_empty();
-
-                for entry in &api_data {
-                    let (id, title, description, genres, released, youtube, runtime) = match entry {
-                        TraktItem::Movie { movie, .. } => (
-                            movie.ids.imdb.clone(),
-                            movie.title.clone(),
-                            movie.overview.clone(),
-                            movie.genres.clone(),
-                            movie.year,
-                            movie.trailer.clone(),
-                            movie.runtime,
-                        ),
-                        TraktItem::Show { show, .. } => (
-                            show.ids.imdb.clone(),
-                            show.title.clone(),
-                            show.overview.clone(),
-                            show.genres.clone(),
-                            show.year,
-                            show.trailer.clone(),
-                            show.runtime,
-                        ),
-                    };
+    // Endpoints accepting Trakt's common filters (genres, years, ...)
+    fn takes_filters(&self) -> bool {
+        matches!(
+            self,
+            TraktEndpoint::Trending
+                | TraktEndpoint::Popular
+                | TraktEndpoint::Anticipated
+                | TraktEndpoint::Watched
+                | TraktEndpoint::Played
+                | TraktEndpoint::Collected
+                | TraktEndpoint::Favorited
+                | TraktEndpoint::Search
+        )
+    }
 
-                    let catalog_type = match entry {
-                        TraktItem::Movie { .. } => CatalogType::Movie,
-                        TraktItem::Show { .. } => CatalogType::Series,
-                    };
+    fn is_paginated(&self) -> bool {
+        !matches!(
+            self,
+            TraktEndpoint::BoxOffice | TraktEndpoint::Recommendations
+        )
+    }
 
-                    let poster = format!("https://images.metahub.space/poster/medium/{}/img", id);
-                    let background =
-                        format!("https://images.metahub.space/background/medium/{}/img", id);
+    fn parse_output(&self, data: Value, catalog_type: &ContentType) -> Result<TraktResponse> {
+        let media: Vec<TraktMedia> = match self {
+            TraktEndpoint::Trending => {
+                let api_data: Vec<TraktTrendingItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            // Popular and Recommendations return bare movies/shows without a wrapper
+            TraktEndpoint::Popular | TraktEndpoint::Recommendations => {
+                match catalog_type.media_kind() {
+                    CatalogType::Movie => {
+                        let api_data: Vec<TraktMovie> = from_value(data)?;
+                        api_data.into_iter().map(TraktMedia::Movie).collect()
+                    }
+                    CatalogType::Series => {
+                        let api_data: Vec<TraktShow> = from_value(data)?;
+                        api_data.into_iter().map(TraktMedia::Show).collect()
+                    }
+                }
+            }
+            TraktEndpoint::Anticipated => {
+                let api_data: Vec<TraktAnticipatedItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            TraktEndpoint::BoxOffice => {
+                let api_data: Vec<TraktBoxOfficeItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            TraktEndpoint::Watched | TraktEndpoint::Played | TraktEndpoint::Collected => {
+                let api_data: Vec<TraktStatsItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            TraktEndpoint::Favorited => {
+                let api_data: Vec<TraktFavoritedItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            TraktEndpoint::Updated => {
+                let api_data: Vec<TraktUpdatedItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            TraktEndpoint::List | TraktEndpoint::Watchlist => {
+                let api_data: Vec<TraktItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+            TraktEndpoint::Search => {
+                let api_data: Vec<TraktSearchItem> = from_value(data)?;
+                api_data.into_iter().map(TraktMedia::from).collect()
+            }
+        };
 
-                    let logo = format!("https://images.metahub.space/logo/medium/{}/img", id);
+        let mut new_catalog_response = CatalogResponse::new_empty();
+        new_catalog_response.metas.extend(
+            media
+                .iter()
+                .filter_map(|entry| catalog_meta_from_media(entry, catalog_type)),
+        );
 
-                    let runtime_string = runtime.map(|e| format!("{} mins", e));
+        Ok(TraktResponse::CatalogResponse(new_catalog_response))
+    }
+}
 
-                    let behavior_hints = DefaultVideoID {
-                        default_video_id: id.clone(),
-                    };
+// Stremio and metahub key off IMDb ids. Titles Trakt has none for, often anime, are looked up
+// in the mapping file by their TMDB or TVDB id and get its IMDb id, or failing that a kitsu: id.
+// Items with neither are skipped. TMDB ids are only trusted for movies as TMDB shares them
+// between types.
+fn stremio_id(ids: &Ids, external_ids: ExternalIds) -> Option<String> {
+    match IdMapping::get() {
+        Ok(mapping) => mapping.stremio_id(ids.imdb.as_deref(), &external_ids),
+        Err(_) => ids.imdb.clone(),
+    }
+}
 
-                    let released_string: Option<String> = released.map(|num| num.to_string());
-
-                    let trailer = match youtube {
-                        Some(youtube_link) => {
-                            let youtube_code = extract_video_id(&youtube_link);
-                            match youtube_code {
-                                Ok(code) => {
-                                    let trailer_object = Trailer {
-                                        source: code.to_string(),
-                                        trailer_type: "Trailer".to_string(),
-                                    };
-                                    Some(trailer_object)
-                                }
-                                Err(_) => None,
-                            }
-                        }
-                        None => None,
-                    };
+/// The id a movie or show is listed under in our catalogs, also used to match watched history
+fn media_stremio_id(entry: &TraktMedia) -> Option<String> {
+    match entry {
+        TraktMedia::Movie(movie) => stremio_id(
+            &movie.ids,
+            ExternalIds {
+                tmdb: movie.ids.tmdb.map(u64::from),
+                ..ExternalIds::default()
+            },
+        ),
+        TraktMedia::Show(show) => stremio_id(
+            &show.ids,
+            ExternalIds {
+                tvdb: show.ids.tvdb.map(u64::from),
+                ..ExternalIds::default()
+            },
+        ),
+    }
+}
 
-                    let meta_item = CatalogMeta {
-                        id,
-                        name: title,
-                        catalog_type,
-                        genres,
-                        release_info: released_string,
-                        background: Some(background),
-                        poster: Some(poster),
-                        description,
-                        behavior_hints: Some(behavior_hints),
-                        trailer,
-                        logo: Some(logo),
-                        runtime: runtime_string,
+fn catalog_meta_from_media(entry: &TraktMedia, catalog_type: &ContentType) -> Option<CatalogMeta> {
+    let id = media_stremio_id(entry)?;
+    let (title, description, genres, released, youtube, runtime) = match entry {
+        TraktMedia::Movie(movie) => (
+            movie.title.clone(),
+            movie.overview.clone(),
+            movie.genres.clone(),
+            movie.year,
+            movie.trailer.clone(),
+            movie.runtime,
+        ),
+        TraktMedia::Show(show) => (
+            show.title.clone(),
+            show.overview.clone(),
+            show.genres.clone(),
+            show.year,
+            show.trailer.clone(),
+            show.runtime,
+        ),
+    };
+
+    // Items of the kind the catalog asked for keep its type, e.g. anime
+    let catalog_type = match (entry, catalog_type.media_kind()) {
+        (TraktMedia::Movie(_), CatalogType::Movie) | (TraktMedia::Show(_), CatalogType::Series) => {
+            catalog_type.clone()
+        }
+        (TraktMedia::Movie(_), _) => ContentType::Movie,
+        (TraktMedia::Show(_), _) => ContentType::Series,
+    };
+
+    let runtime_string = runtime.map(|e| format!("{} mins", e));
+
+    let released_string: Option<String> = released.map(|num| num.to_string());
+
+    let trailer = match youtube {
+        Some(youtube_link) => {
+            let youtube_code = extract_video_id(&youtube_link);
+            match youtube_code {
+                Ok(code) => {
+                    let trailer_object = Trailer {
+                        source: code.to_string(),
+                        trailer_type: "Trailer".to_string(),
                     };
-
-                    new_catalog_response.metas.push(meta_item);
+                    Some(trailer_object)
                 }
-                Ok(TraktResponse::CatalogResponse(new_catalog_response))
+                Err(_) => None,
             }
         }
+        None => None,
+    };
+
+    Some(CatalogMeta {
+        genres,
+        release_info: released_string,
+        description,
+        trailer,
+        runtime: runtime_string,
+        ..CatalogMeta::from_id(id, title, catalog_type)
+    })
+}
+
+// Time window used by the most watched/played/collected/favorited endpoints
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraktPeriod {
+    Daily,
+    #[default]
+    Weekly,
+    Monthly,
+    Yearly,
+    All,
+}
+
+impl TraktPeriod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TraktPeriod::Daily => "daily",
+            TraktPeriod::Weekly => "weekly",
+            TraktPeriod::Monthly => "monthly",
+            TraktPeriod::Yearly => "yearly",
+            TraktPeriod::All => "all",
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraktWatchlistSort {
+    #[default]
+    Rank,
+    Added,
+    Title,
+    Released,
+}
+
+impl TraktWatchlistSort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TraktWatchlistSort::Rank => "rank",
+            TraktWatchlistSort::Added => "added",
+            TraktWatchlistSort::Title => "title",
+            TraktWatchlistSort::Released => "released",
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraktSortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl TraktSortDirection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TraktSortDirection::Asc => "asc",
+            TraktSortDirection::Desc => "desc",
+        }
     }
 }
 
@@ -356,24 +755,35 @@ impl Display for TraktPagination {
     }
 }
 
-pub async fn get_trakt_list_id(url: &str) -> Result<String> {
-    let client = GlobalClient::get()?;
-    let response = client.get(url).send().await?.text().await?;
-    let document = Html::parse_document(&response);
-    let selector = Selector::parse(r#"input[id="list-id"]"#)
-        .map_err(|e| anyhow!("Failed to parse selector: {:?}", e))?;
-    let mut list_id = String::new();
-
-    if let Some(element) = document.select(&selector).next() {
-        if let Some(value) = element.value().attr("value") {
-            list_id.push_str(value);
-        }
+// Convert catalog type to valid string for Trakt API
+fn trakt_media_kind(catalog_type: CatalogType) -> &'static str {
+    match catalog_type {
+        CatalogType::Movie => "movies",
+        CatalogType::Series => "shows",
     }
+}
 
-    match &list_id.is_empty() {
-        true => Err(anyhow!("Trakt List ID not found")),
-        false => Ok(list_id),
+/// Required Trakt API headers, plus the user's bearer token when the config carries one
+async fn trakt_headers(auth: &Option<TraktAuth>) -> Result<HeaderMap> {
+    let env = Environment::get().context("Unable to get global Environment for Trakt query")?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", "application/json".parse()?);
+    headers.insert("trakt-api-key", env.trakt_client_id.parse()?);
+    headers.insert("trakt-api-version", "2".parse()?);
+
+    if let Some(auth) = auth {
+        let auth = auth
+            .refreshed()
+            .await
+            .map_err(|e| anyhow!("Unable to refresh Trakt access token: {}", e.to_string()))?;
+        headers.insert(
+            "Authorization",
+            format!("Bearer {}", auth.access_token).parse()?,
+        );
     }
+
+    Ok(headers)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -382,6 +792,56 @@ pub struct TraktGenre {
     pub slug: String,
 }
 
+/// Trakt's genres for a catalog type, without the catch-all "None" genre
+pub async fn genres(catalog_type: CatalogType) -> Result<Arc<Vec<TraktGenre>>> {
+    if let Some(genres) = GENRES.lock().await.get(&catalog_type) {
+        return Ok(genres.clone());
+    }
+
+    let client = GlobalClient::get()?;
+    let headers = trakt_headers(&None).await?;
+
+    let mut url = Url::parse(TRAKT_API_URL)?;
+    url.path_segments_mut()
+        .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+        .extend(["genres", trakt_media_kind(catalog_type)]);
+
+    let response = client
+        .get(url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let genres: Vec<TraktGenre> = response.json().await.map_err(|e| {
+        anyhow!(
+            "Unable to convert Trakt genres response to json: {}",
+            e.to_string()
+        )
+    })?;
+    let genres: Arc<Vec<TraktGenre>> = Arc::new(
+        genres
+            .into_iter()
+            .filter(|genre| genre.slug != "none")
+            .collect(),
+    );
+
+    GENRES.lock().await.insert(catalog_type, genres.clone());
+    Ok(genres)
+}
+
+// Stremio sends back the genre's name, Trakt filters and tags items by slug
+async fn genre_slug(catalog_type: CatalogType, genre: &str) -> String {
+    let known_slug = genres(catalog_type).await.ok().and_then(|genres| {
+        genres
+            .iter()
+            .find(|known| known.name.eq_ignore_ascii_case(genre) || known.slug == genre)
+            .map(|known| known.slug.clone())
+    });
+
+    known_slug.unwrap_or_else(|| slug(genre))
+}
+
 fn extract_video_id(url: &str) -> Result<&str> {
     // Try to find the index of "v=" in the URL
     if let Some(start) = url.find("v=") {
@@ -0,0 +1,94 @@
+use super::{
+    api::TraktMedia, api::TraktWatchedItem, auth::TraktAuth, media_stremio_id, trakt_headers,
+    TRAKT_API_URL,
+};
+use crate::{addon::catalog::CatalogType, globals::GlobalClient};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+// How long a user's watched history is reused before asking Trakt again
+const WATCHED_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+struct WatchedEntry {
+    fetched_at: Instant,
+    ids: Arc<HashSet<String>>,
+}
+
+// Keyed by the refresh token from the config (stable per user) and the catalog type
+static WATCHED_CACHE: Lazy<Mutex<HashMap<(String, CatalogType), WatchedEntry>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Ids of every movie or show the authenticated user has watched, mapped the same way as the
+/// catalog metas they are filtered against so anime with kitsu: ids are hidden too
+pub async fn watched_ids(
+    auth: &TraktAuth,
+    catalog_type: CatalogType,
+) -> Result<Arc<HashSet<String>>> {
+    let cache_key = (auth.refresh_token.clone(), catalog_type);
+
+    if let Some(entry) = WATCHED_CACHE.lock().await.get(&cache_key) {
+        if entry.fetched_at.elapsed() < WATCHED_CACHE_TTL {
+            return Ok(entry.ids.clone());
+        }
+    }
+
+    let ids = Arc::new(fetch_watched_ids(auth, catalog_type).await?);
+
+    WATCHED_CACHE.lock().await.insert(
+        cache_key,
+        WatchedEntry {
+            fetched_at: Instant::now(),
+            ids: ids.clone(),
+        },
+    );
+
+    Ok(ids)
+}
+
+async fn fetch_watched_ids(auth: &TraktAuth, catalog_type: CatalogType) -> Result<HashSet<String>> {
+    let client = GlobalClient::get()?;
+    let headers = trakt_headers(&Some(auth.clone())).await?;
+
+    let trakt_catalog_type = match catalog_type {
+        CatalogType::Movie => "movies",
+        CatalogType::Series => "shows",
+    };
+
+    let mut url = Url::parse(TRAKT_API_URL)?;
+    url.path_segments_mut()
+        .map_err(|e| anyhow!("Cannot be base URL: {:#?}", e))?
+        .extend(["sync", "watched", trakt_catalog_type]);
+
+    // Season and episode breakdowns are not needed to know a show was watched
+    if let CatalogType::Series = catalog_type {
+        url.query_pairs_mut().append_pair("extended", "noseasons");
+    }
+
+    let watched: Vec<TraktWatchedItem> = client
+        .get(url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Unable to convert Trakt watched history to json: {}",
+                e.to_string()
+            )
+        })?;
+
+    let ids = watched
+        .into_iter()
+        .map(TraktMedia::from)
+        .filter_map(|media| media_stremio_id(&media))
+        .collect();
+
+    Ok(ids)
+}
@@ -0,0 +1,119 @@
+use crate::globals::Environment;
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+type UploadCache = HashMap<(String, String), Arc<dyn Any + Send + Sync>>;
+
+// Stored uploads never change, so once parsed they are kept for the life of the process. Values
+// stored with replace update their entry as they are written.
+static UPLOADS: Lazy<Mutex<UploadCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// User uploads (lists, CSV exports) kept on disk so catalog configs only need a short id.
+/// Each kind of upload gets its own directory under UPLOAD_DIR.
+pub struct UploadStore;
+
+impl UploadStore {
+    /// Stores the value and returns its id. Ids are the SHA-256 of the content, so uploading the
+    /// same thing twice hands back the same id and an upload can't be made to replace another.
+    pub async fn save<T: Serialize>(kind: &str, value: &T) -> Result<String> {
+        let contents = serde_json::to_vec(value).context("Unable to serialize upload")?;
+        let id = format!("{:x}", Sha256::digest(&contents));
+
+        let directory = Self::directory(kind)?;
+        tokio::fs::create_dir_all(&directory)
+            .await
+            .context("Unable to create upload directory")?;
+
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(directory.join(format!("{}.json", id)))
+            .await;
+
+        match file {
+            Ok(mut file) => file
+                .write_all(&contents)
+                .await
+                .context("Unable to write upload")?,
+            // Already stored, the id says the content is the same
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(anyhow!("Unable to write upload: {}", e)),
+        }
+
+        Ok(id)
+    }
+
+    /// Stores a value under an id of the caller's choosing, replacing any value already there.
+    /// For state the server keeps rather than uploads, e.g. rotated tokens.
+    pub async fn replace<T: Serialize + Send + Sync + 'static>(
+        kind: &str,
+        id: &str,
+        value: T,
+    ) -> Result<()> {
+        let contents = serde_json::to_vec(&value).context("Unable to serialize upload")?;
+
+        let directory = Self::directory(kind)?;
+        tokio::fs::create_dir_all(&directory)
+            .await
+            .context("Unable to create upload directory")?;
+
+        // Written aside and renamed over, a reader never sees half a file
+        let path = directory.join(format!("{}.json", id));
+        let partial_path = directory.join(format!("{}.json.partial", id));
+        tokio::fs::write(&partial_path, contents)
+            .await
+            .context("Unable to write upload")?;
+        tokio::fs::rename(&partial_path, &path)
+            .await
+            .context("Unable to write upload")?;
+
+        UPLOADS
+            .lock()
+            .await
+            .insert((kind.to_string(), id.to_string()), Arc::new(value));
+        Ok(())
+    }
+
+    pub async fn load<T: DeserializeOwned + Send + Sync + 'static>(
+        kind: &str,
+        id: &str,
+    ) -> Result<Arc<T>> {
+        // Ids end up in a file path, only ever accept what save hands out. Uploads from before
+        // ids were SHA-256 have 16 digit ids.
+        if !matches!(id.len(), 16 | 64) || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("Invalid upload id: {}", id));
+        }
+
+        let cache_key = (kind.to_string(), id.to_string());
+        if let Some(upload) = UPLOADS.lock().await.get(&cache_key) {
+            if let Ok(upload) = upload.clone().downcast::<T>() {
+                return Ok(upload);
+            }
+        }
+
+        let path = Self::directory(kind)?.join(format!("{}.json", id));
+        let contents = tokio::fs::read(&path)
+            .await
+            .map_err(|_| anyhow!("No upload found with id {}", id))?;
+
+        let upload: Arc<T> =
+            Arc::new(serde_json::from_slice(&contents).context("Unable to read stored upload")?);
+        UPLOADS.lock().await.insert(cache_key, upload.clone());
+
+        Ok(upload)
+    }
+
+    fn directory(kind: &str) -> Result<PathBuf> {
+        let env = Environment::get().context("Unable to get global Environment for uploads")?;
+        Ok(PathBuf::from(&env.upload_dir).join(kind))
+    }
+}